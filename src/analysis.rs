@@ -0,0 +1,252 @@
+//! Analysis exports of `Universe`: probes, orbits, profiles and velocity fields. Compiled
+//! with the `analysis` feature.
+
+use crate::bounds::Bounds;
+use crate::Universe;
+use wasm_bindgen::prelude::*;
+
+/// Upper bound for the `steps` of `Universe::predict_orbit`.
+pub const MAX_PREDICTION_STEPS: u32 = 10_000;
+
+#[wasm_bindgen]
+impl Universe {
+    /// Adds a rectangle with lower corner `(x, y)` whose contents are reported by
+    /// `probe_data`. Probes only observe, they do not affect particles. Returns its id.
+    pub fn add_probe(&mut self, x: f64, y: f64, w: f64, h: f64) -> u32 {
+        self.next_probe_id += 1;
+        self.probes.push((self.next_probe_id - 1, Bounds::new(x, y, w, h)));
+        self.next_probe_id - 1
+    }
+
+    pub fn remove_probe(&mut self, id: u32) -> bool {
+        let len = self.probes.len();
+        self.probes.retain(|(probe, _)| *probe != id);
+        self.probes.len() < len
+    }
+
+    /// `[count, mass, vx, vy, kinetic_energy]` of the particles currently inside probe `id`,
+    /// with the mass-weighted mean velocity. Empty for unknown ids.
+    pub fn probe_data(&self, id: u32) -> Vec<f64> {
+        self.probes
+            .iter()
+            .find(|(probe, _)| *probe == id)
+            .map_or(Vec::new(), |(_, region)| self.phys.region_stats(region).to_vec())
+    }
+
+    /// `probe_data` of every probe in the order they were added, each prefixed by the
+    /// probe's id: `[id, count, mass, vx, vy, kinetic_energy, id, ...]`.
+    pub fn all_probe_data(&self) -> Vec<f64> {
+        self.probes
+            .iter()
+            .flat_map(|(id, region)| {
+                let stats = self.phys.region_stats(region);
+                std::iter::once(f64::from(*id)).chain(stats.to_vec())
+            })
+            .collect()
+    }
+
+    /// `[count, mass, x, y, vx, vy, velocity_dispersion, rms_radius]` of the particles with
+    /// origin code `origin`, e.g. a satellite added with `add_particle_with_origin`. Position
+    /// and velocity are the mass-weighted means; the dispersion and the radius are RMS
+    /// deviations from them.
+    pub fn group_stats(&self, origin: u16) -> Vec<f64> {
+        self.phys.group_stats(origin).to_vec()
+    }
+
+    /// Acceleration `[ax, ay]` a test mass at `(x, y)` would feel, without adding a particle.
+    pub fn probe_acceleration(&self, x: f64, y: f64) -> Vec<f64> {
+        self.phys.field_at(&[x, y]).to_vec()
+    }
+
+    /// Path `[x0, y0, x1, y1, ...]` a particle placed at `(x, y)` with velocity `(vx, vy)`
+    /// would take, sampled every `stride` ticks for `steps` ticks, at most
+    /// `MAX_PREDICTION_STEPS`. The other particles are held in place and the new one does not
+    /// pull on them, so `mass` has no effect. Cheap enough for a preview while dragging, and
+    /// nothing in the universe changes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn predict_orbit(
+        &self,
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+        _mass: f64,
+        steps: u32,
+        stride: u32,
+    ) -> Vec<f64> {
+        let steps = steps.min(MAX_PREDICTION_STEPS) as usize;
+        self.phys
+            .predict([x, y], [vx, vy], steps, stride as usize)
+            .iter()
+            .flat_map(|p| p.to_vec())
+            .collect()
+    }
+
+    /// Launch velocity that takes a particle from `(x0, y0)` to `(x1, y1)` in `flight_ticks`
+    /// ticks through the field of `predict_orbit`, as `[vx, vy, miss_distance]`. Starting
+    /// from the straight line, each of the `iterations` attempts corrects the velocity by
+    /// part of the miss divided by the flight time. The best attempt is returned even if it
+    /// did not converge, so a large miss distance means the target was not reached.
+    pub fn solve_launch(
+        &self,
+        x0: f64,
+        y0: f64,
+        x1: f64,
+        y1: f64,
+        flight_ticks: u32,
+        iterations: u32,
+    ) -> Vec<f64> {
+        const DAMPING: f64 = 0.7;
+        let ticks = flight_ticks.clamp(1, MAX_PREDICTION_STEPS) as usize;
+        let t = ticks as f64;
+        let mut v = [(x1 - x0) / t, (y1 - y0) / t];
+        let mut best = vec![v[0], v[1], f64::INFINITY];
+        for _ in 0..iterations.max(1) {
+            let end = self.phys.predict([x0, y0], v, ticks, ticks)[0];
+            let miss = [x1 - end[0], y1 - end[1]];
+            let distance = miss[0].hypot(miss[1]);
+            if distance < best[2] {
+                best = vec![v[0], v[1], distance];
+            } else if !distance.is_finite() {
+                break;
+            }
+            v = [v[0] + DAMPING * miss[0] / t, v[1] + DAMPING * miss[1] / t];
+        }
+        best
+    }
+
+    /// Tidal tensor `[dax/dx, dax/dy, day/dx, day/dy]` at `(x, y)`, the gradient of
+    /// `probe_acceleration` by central differences. Positive diagonal entries stretch,
+    /// negative ones squeeze.
+    pub fn tidal_tensor_at(&self, x: f64, y: f64) -> Vec<f64> {
+        let h = 1e-4 * x.hypot(y).max(1.0);
+        let dx = self.phys.field_at(&[x + h, y]);
+        let dx_neg = self.phys.field_at(&[x - h, y]);
+        let dy = self.phys.field_at(&[x, y + h]);
+        let dy_neg = self.phys.field_at(&[x, y - h]);
+        vec![
+            (dx[0] - dx_neg[0]) / (2.0 * h),
+            (dy[0] - dy_neg[0]) / (2.0 * h),
+            (dx[1] - dx_neg[1]) / (2.0 * h),
+            (dy[1] - dy_neg[1]) / (2.0 * h),
+        ]
+    }
+
+    /// `[fx, fy]`, the gravitational force particle `j` alone exerts on particle `i`.
+    /// Empty if either index is out of range.
+    pub fn pair_force(&self, i: usize, j: usize) -> Vec<f64> {
+        let n = self.phys.elements.len();
+        if i < n && j < n {
+            self.phys.pair_force(i, j).to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Kinetic energy `[total, bulk translation, rotation, random]` in the absolute frame, to
+    /// tell bulk motion from the random motion of a dynamically hot system. Rotation is the
+    /// rigid rotation about the center of mass carrying the same angular momentum, random
+    /// motion the remainder. A single O(n) pass.
+    pub fn energy_decomposition(&self) -> Vec<f64> {
+        self.phys.energy_decomposition().to_vec()
+    }
+
+    /// Two-body orbit of every particle about particle `central`, as rows `[specific
+    /// energy, semi-major axis, eccentricity]` in particle order. Meaningful when `central`
+    /// dominates the mass; negative energies are bound orbits. The row of `central` is NaN.
+    /// Empty if `central` is out of range.
+    pub fn orbital_elements_about(&self, central: usize) -> Vec<f64> {
+        if central >= self.phys.elements.len() {
+            return Vec::new();
+        }
+        self.phys
+            .orbital_elements_about(central)
+            .iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Radius about the center of mass that encloses half of the total mass, a single size
+    /// to normalize plots by.
+    pub fn half_mass_radius(&self) -> f64 {
+        self.phys.half_mass_radius()
+    }
+
+    /// Mass within each of `radii` of the center of mass, e.g. for rotation curves.
+    pub fn enclosed_mass_profile(&self, radii: Vec<f64>) -> Vec<f64> {
+        self.phys.enclosed_mass_profile(&radii)
+    }
+
+    /// Free-fall time `sqrt(3π / (32 G ρ))`, with `ρ` the total mass divided by the area of
+    /// the particles' bounding box. Infinite without mass or gravity.
+    pub fn free_fall_time(&self) -> f64 {
+        let area = self.phys.bounds().map_or(0.0, |b| b.area());
+        let mass: f64 = self.phys.elements.iter().map(|e| e.mass).sum();
+        let g = self.phys.gravitational_constant();
+        if mass <= 0.0 || g <= 0.0 {
+            return f64::INFINITY;
+        }
+        let density = mass / area;
+        (3.0 * std::f64::consts::PI / (32.0 * g * density)).sqrt()
+    }
+
+    /// Mass-weighted mean velocity `[vx, vy]` of the particles inside the rectangle from
+    /// `(x, y)` with size `w` x `h`, zero if it is empty. The lower edges are inside, the
+    /// upper ones are not.
+    pub fn bulk_velocity_in_region(&self, x: f64, y: f64, w: f64, h: f64) -> Vec<f64> {
+        self.phys.bulk_velocity_in(&Bounds::new(x, y, w, h)).to_vec()
+    }
+
+    /// Mass-weighted mean velocity `[vx, vy]` per cell of an `nx` x `ny` grid over the
+    /// particles' bounding box, row by row from the lower corner. Empty cells are zero.
+    pub fn velocity_grid(&self, nx: u32, ny: u32) -> Vec<f64> {
+        let (_, drift) = self.reference_frame();
+        self.phys
+            .velocity_grid(nx as usize, ny as usize, &drift)
+            .iter()
+            .flat_map(|v| v.to_vec())
+            .collect()
+    }
+
+    /// Curl of `velocity_grid` per cell, twice the local angular velocity.
+    pub fn vorticity_grid(&self, nx: u32, ny: u32) -> Vec<f64> {
+        self.phys.vorticity_grid(nx as usize, ny as usize)
+    }
+
+    /// Divergence of `velocity_grid` per cell, positive where the particles spread out.
+    pub fn divergence_grid(&self, nx: u32, ny: u32) -> Vec<f64> {
+        self.phys.divergence_grid(nx as usize, ny as usize)
+    }
+
+    /// Number of particles within `radius` of `(x, y)`, for example near the cursor. The point
+    /// is relative to the reference particle if one is set, like the positions.
+    pub fn count_within(&self, x: f64, y: f64, radius: f64) -> u32 {
+        let (origin, _) = self.reference_frame();
+        self.phys.count_within(&[x + origin[0], y + origin[1]], &radius) as u32
+    }
+
+    /// `[index, speed]` of the fastest particle in the absolute frame, empty without
+    /// particles. Useful to spot runaway particles.
+    pub fn fastest_particle(&self) -> Vec<f64> {
+        self.phys
+            .elements
+            .iter()
+            .map(|e| e.direction_vector[0].hypot(e.direction_vector[1]))
+            .enumerate()
+            .fold(None, |fastest: Option<(usize, f64)>, (i, speed)| match fastest {
+                Some((_, max)) if max >= speed => fastest,
+                _ => Some((i, speed)),
+            })
+            .map_or(Vec::new(), |(i, speed)| vec![i as f64, speed])
+    }
+
+    /// Indices of particles with positive total specific energy, which will eventually escape.
+    pub fn unbound_indices(&self) -> Vec<u32> {
+        self.phys
+            .unbound_indices()
+            .into_iter()
+            .map(|i| i as u32)
+            .collect()
+    }
+}
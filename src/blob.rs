@@ -0,0 +1,44 @@
+//! Packed initial conditions, to load many particles from a single buffer.
+//!
+//! Layout, all values little-endian:
+//!
+//! | Offset | Type  | Content                                         |
+//! |--------|-------|-------------------------------------------------|
+//! | 0      | `u32` | Particle count `n`                              |
+//! | 4      | `f32` | `n` records `x, y, vx, vy, mass`, 20 bytes each |
+//!
+//! The buffer must be exactly `4 + 20 n` bytes long.
+
+use crate::physics::PhysicsObject;
+
+pub const RECORD_BYTES: usize = 20;
+
+/// Elements of a blob, `None` if its length does not match the count or a value is not
+/// finite or a mass is negative.
+pub fn decode(bytes: &[u8]) -> Option<Vec<PhysicsObject<f64>>> {
+    let mut count = [0u8; 4];
+    count.copy_from_slice(bytes.get(0..4)?);
+    let count = u32::from_le_bytes(count) as usize;
+    let records = &bytes[4..];
+    if records.len() != count.checked_mul(RECORD_BYTES)? {
+        return None;
+    }
+    records
+        .chunks_exact(RECORD_BYTES)
+        .map(|record| {
+            let v: Vec<f64> = record
+                .chunks_exact(4)
+                .map(|chunk| {
+                    let mut le = [0u8; 4];
+                    le.copy_from_slice(chunk);
+                    f64::from(f32::from_le_bytes(le))
+                })
+                .collect();
+            if v.iter().all(|v| v.is_finite()) && v[4] >= 0.0 {
+                Some(PhysicsObject::new([v[0], v[1]], [v[2], v[3]], v[4]))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,65 @@
+/// Axis-aligned rectangle, `x`/`y` being the lower corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Bounds {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Bounds {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Half-open containment: the lower edges are inside, the upper edges are not.
+    pub fn contains(&self, px: f64, py: f64) -> bool {
+        px >= self.x && px < self.x + self.width && py >= self.y && py < self.y + self.height
+    }
+
+    /// Closed overlap test, so boxes that only touch along an edge or corner intersect.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+
+    /// Squared distance from the point to the closest point of the box, zero if inside.
+    pub fn distance_squared_to_point(&self, px: f64, py: f64) -> f64 {
+        let dx = (self.x - px).max(0.0).max(px - (self.x + self.width));
+        let dy = (self.y - py).max(0.0).max(py - (self.y + self.height));
+        dx * dx + dy * dy
+    }
+
+    /// Grows the box by `margin` on every side.
+    pub fn expand(&self, margin: f64) -> Bounds {
+        Bounds::new(
+            self.x - margin,
+            self.y - margin,
+            self.width + 2.0 * margin,
+            self.height + 2.0 * margin,
+        )
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Bounds::new(
+            x,
+            y,
+            (self.x + self.width).max(other.x + other.width) - x,
+            (self.y + self.height).max(other.y + other.height) - y,
+        )
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width * self.height
+    }
+}
@@ -0,0 +1,306 @@
+use crate::physics::{PhysicsObject, PhysicsSpace};
+use crate::rng::SimRng;
+use crate::types::EuclideanSpace;
+use rand::distributions::StandardNormal;
+use rand::{FromEntropy, Rng, SeedableRng};
+
+#[derive(Debug, Clone)]
+enum Component {
+    Particle {
+        position: [f64; 2],
+        velocity: [f64; 2],
+        mass: f64,
+    },
+    Box {
+        count: u32,
+        min: [f64; 2],
+        max: [f64; 2],
+        speed_range: f64,
+        particle_mass: f64,
+    },
+    Disk {
+        count: u32,
+        center: [f64; 2],
+        radius: f64,
+        particle_mass: f64,
+        central_mass: f64,
+        velocity_dispersion: f64,
+    },
+    Plummer {
+        count: u32,
+        center: [f64; 2],
+        scale_radius: f64,
+        particle_mass: f64,
+    },
+    ColdDisk {
+        count: u32,
+        center: [f64; 2],
+        radius: f64,
+        particle_mass: f64,
+    },
+}
+
+/// Chainable description of a scene, turned into a `PhysicsSpace` by `build`.
+///
+/// Components are sampled in the order they were added, so a seeded builder always
+/// produces the same scene.
+#[derive(Debug, Clone)]
+pub struct UniverseBuilder {
+    seed: Option<u64>,
+    gravitational_constant: f64,
+    radius: f64,
+    merge_distance: f64,
+    components: Vec<Component>,
+}
+
+impl Default for UniverseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UniverseBuilder {
+    /// Starts from the settings of the default demo, without any particles.
+    pub fn new() -> Self {
+        UniverseBuilder {
+            seed: None,
+            gravitational_constant: 100.0,
+            radius: 3000.0,
+            merge_distance: 5.0,
+            components: Vec::new(),
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn gravitational_constant(mut self, g: f64) -> Self {
+        self.gravitational_constant = g;
+        self
+    }
+
+    /// Particles further than this away from [0,0] get deleted.
+    pub fn radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Particles closer than this get merged.
+    pub fn merge_distance(mut self, distance: f64) -> Self {
+        self.merge_distance = distance;
+        self
+    }
+
+    pub fn add_particle(mut self, x: f64, y: f64, vx: f64, vy: f64, mass: f64) -> Self {
+        self.components.push(Component::Particle {
+            position: [x, y],
+            velocity: [vx, vy],
+            mass,
+        });
+        self
+    }
+
+    /// `count` particles uniformly placed in the rectangle from `min` to `max`, with each
+    /// velocity component uniform in `[-speed_range, speed_range)`.
+    pub fn add_box(
+        mut self,
+        count: u32,
+        min: [f64; 2],
+        max: [f64; 2],
+        speed_range: f64,
+        particle_mass: f64,
+    ) -> Self {
+        self.components.push(Component::Box {
+            count,
+            min,
+            max,
+            speed_range,
+            particle_mass,
+        });
+        self
+    }
+
+    /// A central body of `central_mass` surrounded by `count` particles uniformly placed in
+    /// a disk, each on a circular orbit around the mass enclosed by its radius. A non-zero
+    /// `velocity_dispersion` adds Gaussian random velocities with that standard deviation per
+    /// component, which keeps the disk from fragmenting right away.
+    pub fn add_disk(
+        mut self,
+        count: u32,
+        center: [f64; 2],
+        radius: f64,
+        particle_mass: f64,
+        central_mass: f64,
+        velocity_dispersion: f64,
+    ) -> Self {
+        self.components.push(Component::Disk {
+            count,
+            center,
+            radius,
+            particle_mass,
+            central_mass,
+            velocity_dispersion,
+        });
+        self
+    }
+
+    /// `count` particles following a Plummer profile with the given scale radius, with
+    /// isotropic random velocities matching the local Plummer velocity dispersion.
+    pub fn add_plummer(
+        mut self,
+        count: u32,
+        center: [f64; 2],
+        scale_radius: f64,
+        particle_mass: f64,
+    ) -> Self {
+        self.components.push(Component::Plummer {
+            count,
+            center,
+            scale_radius,
+            particle_mass,
+        });
+        self
+    }
+
+    /// `count` particles uniformly placed in a disk, all at rest and without a central body.
+    pub fn add_cold_disk(
+        mut self,
+        count: u32,
+        center: [f64; 2],
+        radius: f64,
+        particle_mass: f64,
+    ) -> Self {
+        self.components.push(Component::ColdDisk {
+            count,
+            center,
+            radius,
+            particle_mass,
+        });
+        self
+    }
+
+    pub fn build(&self) -> PhysicsSpace<f64, EuclideanSpace<f64>> {
+        let mut rng = match self.seed {
+            Some(seed) => SimRng::seed_from_u64(seed),
+            None => SimRng::from_entropy(),
+        };
+        let mut elements = Vec::new();
+        for component in self.components.iter() {
+            self.sample(component, &mut rng, &mut elements);
+        }
+        PhysicsSpace::new(
+            elements,
+            self.gravitational_constant,
+            EuclideanSpace::<f64> {
+                field: std::marker::PhantomData::<f64>,
+            },
+            self.radius,
+            self.merge_distance,
+        )
+    }
+
+    fn sample<R: Rng>(
+        &self,
+        component: &Component,
+        rng: &mut R,
+        elements: &mut Vec<PhysicsObject<f64>>,
+    ) {
+        let g = self.gravitational_constant;
+        match *component {
+            Component::Particle {
+                position,
+                velocity,
+                mass,
+            } => elements.push(PhysicsObject::new(position, velocity, mass)),
+            Component::Box {
+                count,
+                min,
+                max,
+                speed_range,
+                particle_mass,
+            } => {
+                for _ in 0..count {
+                    elements.push(PhysicsObject::new(
+                        [rng.gen_range(min[0], max[0]), rng.gen_range(min[1], max[1])],
+                        [
+                            rng.gen_range(-speed_range, speed_range),
+                            rng.gen_range(-speed_range, speed_range),
+                        ],
+                        particle_mass,
+                    ))
+                }
+            }
+            Component::Disk {
+                count,
+                center,
+                radius,
+                particle_mass,
+                central_mass,
+                velocity_dispersion,
+            } => {
+                elements.push(PhysicsObject::new(center, [0.0, 0.0], central_mass));
+                let disk_mass = f64::from(count) * particle_mass;
+                for _ in 0..count {
+                    let r = radius * rng.gen::<f64>().sqrt();
+                    let angle = rng.gen_range(0.0, 2.0 * std::f64::consts::PI);
+                    let (sin, cos) = angle.sin_cos();
+                    let enclosed = central_mass + disk_mass * (r / radius).powi(2);
+                    let speed = if r > 0.0 { (g * enclosed / r).sqrt() } else { 0.0 };
+                    let mut velocity = [-speed * sin, speed * cos];
+                    if velocity_dispersion > 0.0 {
+                        let vx: f64 = rng.sample(StandardNormal);
+                        let vy: f64 = rng.sample(StandardNormal);
+                        velocity[0] += velocity_dispersion * vx;
+                        velocity[1] += velocity_dispersion * vy;
+                    }
+                    elements.push(PhysicsObject::new(
+                        [center[0] + r * cos, center[1] + r * sin],
+                        velocity,
+                        particle_mass,
+                    ))
+                }
+            }
+            Component::Plummer {
+                count,
+                center,
+                scale_radius,
+                particle_mass,
+            } => {
+                let total_mass = f64::from(count) * particle_mass;
+                for _ in 0..count {
+                    // Invert the cumulative mass profile, cutting off the far tail
+                    let u: f64 = rng.gen_range(1e-3, 0.99);
+                    let r = scale_radius / (u.powf(-2.0 / 3.0) - 1.0).sqrt();
+                    let angle = rng.gen_range(0.0, 2.0 * std::f64::consts::PI);
+                    let softened_r = (r * r + scale_radius * scale_radius).sqrt();
+                    let sigma = (g * total_mass / (6.0 * softened_r)).sqrt();
+                    let vx: f64 = rng.sample(StandardNormal);
+                    let vy: f64 = rng.sample(StandardNormal);
+                    elements.push(PhysicsObject::new(
+                        [center[0] + r * angle.cos(), center[1] + r * angle.sin()],
+                        [sigma * vx, sigma * vy],
+                        particle_mass,
+                    ))
+                }
+            }
+            Component::ColdDisk {
+                count,
+                center,
+                radius,
+                particle_mass,
+            } => {
+                for _ in 0..count {
+                    let r = radius * rng.gen::<f64>().sqrt();
+                    let angle = rng.gen_range(0.0, 2.0 * std::f64::consts::PI);
+                    elements.push(PhysicsObject::new(
+                        [center[0] + r * angle.cos(), center[1] + r * angle.sin()],
+                        [0.0, 0.0],
+                        particle_mass,
+                    ))
+                }
+            }
+        }
+    }
+}
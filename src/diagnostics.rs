@@ -0,0 +1,229 @@
+//! Diagnostic exports of `Universe`: the stability monitor, tick timing, benchmarks and
+//! conservation checks. Compiled with the `diagnostics` feature.
+
+use crate::physics::Divergence;
+use crate::utils::{self, LogLevel};
+use crate::Universe;
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+/// Ticks in a row a limit of the stability monitor must be exceeded before it warns.
+pub const UNSTABLE_TICKS: u32 = 3;
+/// Largest fraction of fast particles the stability monitor accepts.
+pub const MAX_FAST_FRACTION: f64 = 0.05;
+
+/// Number of recent ticks `Universe::avg_tick_ms` averages over.
+pub const TICK_TIME_WINDOW: usize = 60;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance)]
+    fn now() -> f64;
+}
+
+/// Milliseconds since an arbitrary start, from `performance.now()` in the browser.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn clock_ms() -> f64 {
+    now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn clock_ms() -> f64 {
+    thread_local!(static START: std::time::Instant = std::time::Instant::now());
+    START.with(|start| start.elapsed().as_secs_f64() * 1000.0)
+}
+
+/// State of the stability monitor and the tick timer.
+#[derive(Clone)]
+pub(crate) struct Diagnostics {
+    pub stability_monitor: bool,
+    pub unstable_ticks: [u32; 2], //Consecutive ticks with too many fast particles and with NaNs
+    pub stability_warning: Option<String>,
+    pub tick_times: VecDeque<f64>, //Wall-clock milliseconds of the last `TICK_TIME_WINDOW` ticks
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Diagnostics {
+            stability_monitor: true,
+            unstable_ticks: [0, 0],
+            stability_warning: None,
+            tick_times: VecDeque::new(),
+        }
+    }
+}
+
+/// Throughput measured by `Universe::benchmark`.
+#[wasm_bindgen]
+pub struct BenchmarkResult {
+    ticks: u32,
+    particles: u32,
+    total_ms: f64,
+}
+
+#[wasm_bindgen]
+impl BenchmarkResult {
+    pub fn ticks_per_second(&self) -> f64 {
+        f64::from(self.ticks) / self.total_ms * 1000.0
+    }
+
+    /// Ticks per second times the number of particles at the start of the run.
+    pub fn particle_ticks_per_second(&self) -> f64 {
+        self.ticks_per_second() * f64::from(self.particles)
+    }
+
+    /// Average wall time of one tick. The force computation is done during integration,
+    /// there is no separate tree build to time.
+    pub fn tick_ms_avg(&self) -> f64 {
+        self.total_ms / f64::from(self.ticks)
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    /// `[fx, fy]`, the sum of mass times acceleration over all particles. Gravity between
+    /// particles cancels pairwise, so anything beyond rounding errors points to an external
+    /// force or an asymmetric force evaluation. With the `"hybrid"` integrator, each particle's
+    /// acceleration is taken against the others' previous positions, so expect a small
+    /// residual there.
+    pub fn net_force(&self) -> Vec<f64> {
+        self.phys.net_force().to_vec()
+    }
+
+    /// Net torque about `(x, y)` from the current accelerations. Non-zero values indicate
+    /// an external influence, as internal torques cancel.
+    pub fn net_torque_about(&self, x: f64, y: f64) -> f64 {
+        self.phys.net_torque_about(&[x, y])
+    }
+
+    /// Diagnosis of the last instability the monitor found, `None` while the simulation looks
+    /// stable. The warning stays until `clear_stability_warning` is called.
+    pub fn stability_warning(&self) -> Option<String> {
+        self.diagnostics.stability_warning.clone()
+    }
+
+    pub fn clear_stability_warning(&mut self) {
+        self.diagnostics.stability_warning = None;
+        self.diagnostics.unstable_ticks = [0, 0];
+    }
+
+    /// Enables or disables the per-tick stability monitor, on by default.
+    pub fn set_stability_monitor(&mut self, enabled: bool) {
+        self.diagnostics.stability_monitor = enabled;
+        self.diagnostics.unstable_ticks = [0, 0];
+    }
+
+    /// Magnitude of the largest particle acceleration computed during the last tick, recorded
+    /// while integrating. Large values point to close encounters the time step cannot resolve.
+    pub fn max_acceleration(&self) -> f64 {
+        self.phys.max_acceleration()
+    }
+
+    /// `[kinetic energy, potential energy, px, py, angular momentum, center of mass x, y]` in
+    /// the absolute frame, with the angular momentum taken about the origin. Everything but
+    /// the potential comes from a single O(n) pass. The potential is O(n²) and is NaN unless
+    /// `include_potential` is set.
+    pub fn conserved_quantities(&self, include_potential: bool) -> Vec<f64> {
+        let [kinetic, px, py, l, x, y] = self.phys.conserved_sums();
+        let potential = if include_potential {
+            self.phys.potential_energy()
+        } else {
+            f64::NAN
+        };
+        vec![kinetic, potential, px, py, l, x, y]
+    }
+
+    /// Runs `ticks` ticks on a copy of the current state and measures them with
+    /// `performance.now()`. The simulation itself is not advanced, and the cull callback is
+    /// not called for the copy.
+    pub fn benchmark(&self, ticks: u32) -> BenchmarkResult {
+        let mut copy = self.clone();
+        copy.cull_callback = None;
+        let particles = self.particle_count();
+        let start = clock_ms();
+        for _ in 0..ticks {
+            copy.step();
+        }
+        BenchmarkResult {
+            ticks,
+            particles,
+            total_ms: clock_ms() - start,
+        }
+    }
+
+    /// Average wall-clock duration of the last `TICK_TIME_WINDOW` ticks in milliseconds, zero
+    /// before the first tick. Measured inside the module, so it is not blurred by the coarse
+    /// clocks JS gets across the wasm boundary.
+    pub fn avg_tick_ms(&self) -> f64 {
+        let times = &self.diagnostics.tick_times;
+        if times.is_empty() {
+            return 0.0;
+        }
+        times.iter().sum::<f64>() / times.len() as f64
+    }
+
+    /// How far this universe has diverged from `other`, e.g. between two runs of the same
+    /// start with different settings. Particles are matched by id, so reordering does not
+    /// count as a difference.
+    pub fn compare(&self, other: &Universe) -> Divergence {
+        self.phys.compare(&other.phys)
+    }
+}
+
+impl Universe {
+    /// Counts particles faster than a tenth of the culling radius per tick and the elements
+    /// removed by the NaN guard, and sets the stability warning once either stays above its
+    /// limit for `UNSTABLE_TICKS` ticks in a row. Does nothing while the monitor is off.
+    pub(crate) fn check_stability(&mut self) {
+        if !self.diagnostics.stability_monitor {
+            return;
+        }
+        let limit = self.phys.cull_radius().map_or(f64::INFINITY, |r| r / 10.0);
+        let limit = limit * limit;
+        let (mut fast, mut non_finite) = (0, self.phys.report().non_finite_removed);
+        for e in self.phys.elements.iter() {
+            let v = e.direction_vector;
+            match (v[0] * v[0] + v[1] * v[1]).partial_cmp(&limit) {
+                Some(std::cmp::Ordering::Greater) => fast += 1,
+                None => non_finite += 1,
+                _ => {}
+            }
+        }
+        let n = self.phys.elements.len();
+        let fraction = if n == 0 { 0.0 } else { fast as f64 / n as f64 };
+        let streak = |ticks: &mut u32, unstable: bool| {
+            *ticks = if unstable { *ticks + 1 } else { 0 };
+            *ticks >= UNSTABLE_TICKS
+        };
+        let diagnostics = &mut self.diagnostics;
+        let warned = diagnostics.stability_warning.is_some();
+        // Both streaks are updated every tick, also while the other one warns
+        let non_finite_streak = streak(&mut diagnostics.unstable_ticks[1], non_finite > 0);
+        let fast_streak = streak(&mut diagnostics.unstable_ticks[0], fraction > MAX_FAST_FRACTION);
+        if non_finite_streak {
+            diagnostics.stability_warning = Some(format!(
+                "non-finite values for {} ticks in a row, {} particles this tick: lower G or \
+                 raise the softening",
+                diagnostics.unstable_ticks[1], non_finite
+            ));
+        } else if fast_streak {
+            diagnostics.stability_warning = Some(format!(
+                "dt too large for current G/softening: {:.0}% of particles move more than a \
+                 tenth of the culling radius per tick",
+                fraction * 100.0
+            ));
+        }
+        if let (false, Some(warning)) = (warned, &diagnostics.stability_warning) {
+            utils::log(LogLevel::Warn, warning);
+        }
+    }
+
+    /// Adds the duration of a tick that started at `started`, from `clock_ms`.
+    pub(crate) fn record_tick_time(&mut self, started: f64) {
+        let times = &mut self.diagnostics.tick_times;
+        if times.len() == TICK_TIME_WINDOW {
+            times.pop_front();
+        }
+        times.push_back(clock_ms() - started);
+    }
+}
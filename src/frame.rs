@@ -0,0 +1,73 @@
+//! Packed particle frames, to move state between a simulation worker and the main thread
+//! as a transferable buffer.
+//!
+//! Layout of version 1, all values little-endian:
+//!
+//! | Offset | Type  | Content                                            |
+//! |--------|-------|----------------------------------------------------|
+//! | 0      | `u32` | `VERSION`                                          |
+//! | 4      | `u32` | Channel flags, see `VELOCITIES` and `MASSES`       |
+//! | 8      | `u32` | Particle count `n`                                 |
+//! | 12     | `u32` | Frame number, the tick count of the universe       |
+//! | 16     | `f64` | `n` positions `x, y`                               |
+//! |        | `f64` | `n` velocities `vx, vy`, if `VELOCITIES` is set    |
+//! |        | `f64` | `n` masses, if `MASSES` is set                     |
+//!
+//! The header is 16 bytes long, so every channel can be viewed as a `Float64Array` in place.
+
+use crate::physics::PhysicsObject;
+
+pub const VERSION: u32 = 1;
+pub const HEADER_BYTES: usize = 16;
+/// Channel flag for velocities
+pub const VELOCITIES: u32 = 1;
+/// Channel flag for masses
+pub const MASSES: u32 = 2;
+
+/// Packs `elements` into a frame with the given channels. Unknown channel bits are ignored.
+pub fn encode(frame: u32, channels: u32, elements: &[PhysicsObject<f64>]) -> Vec<u8> {
+    let channels = channels & (VELOCITIES | MASSES);
+    let mut bytes = Vec::with_capacity(HEADER_BYTES + 40 * elements.len());
+    for value in [VERSION, channels, elements.len() as u32, frame].iter() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    let mut push = |values: &[f64]| {
+        values
+            .iter()
+            .for_each(|v| bytes.extend_from_slice(&v.to_le_bytes()))
+    };
+    elements.iter().for_each(|e| push(&e.position_vector));
+    if channels & VELOCITIES != 0 {
+        elements.iter().for_each(|e| push(&e.direction_vector));
+    }
+    if channels & MASSES != 0 {
+        elements.iter().for_each(|e| push(&[e.mass]));
+    }
+    bytes
+}
+
+/// Positions `[x0, y0, x1, y1, ...]` of a frame, `None` if `bytes` is not a complete frame
+/// of this version.
+pub fn decode_positions(bytes: &[u8]) -> Option<Vec<f64>> {
+    let word = |i: usize| {
+        let mut le = [0u8; 4];
+        le.copy_from_slice(bytes.get(4 * i..4 * i + 4)?);
+        Some(u32::from_le_bytes(le) as usize)
+    };
+    if word(0)? != VERSION as usize {
+        return None;
+    }
+    let count = word(2)?;
+    let end = count.checked_mul(16)?.checked_add(HEADER_BYTES)?;
+    let positions = bytes.get(HEADER_BYTES..end)?;
+    Some(
+        positions
+            .chunks_exact(8)
+            .map(|chunk| {
+                let mut le = [0u8; 8];
+                le.copy_from_slice(chunk);
+                f64::from_le_bytes(le)
+            })
+            .collect(),
+    )
+}
@@ -1,34 +1,137 @@
 extern crate wasm_bindgen;
 
-mod physics;
-mod types;
+#[cfg(feature = "analysis")]
+mod analysis;
+#[cfg(feature = "snapshots")]
+pub mod blob;
+pub mod bounds;
+pub mod builder;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+pub mod frame;
+pub mod physics;
+pub mod plugin;
+pub mod prelude;
+#[cfg(feature = "recording")]
+mod recording;
+pub mod rng;
+#[cfg(feature = "scheduling")]
+mod scheduling;
+#[cfg(feature = "snapshots")]
+mod snapshots;
+pub mod types;
 mod utils;
 
-use physics::{PhysicsObject, PhysicsSpace};
+#[cfg(feature = "analysis")]
+pub use analysis::MAX_PREDICTION_STEPS;
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::{BenchmarkResult, MAX_FAST_FRACTION, TICK_TIME_WINDOW, UNSTABLE_TICKS};
+
+use builder::UniverseBuilder;
+use physics::{
+    AbsorberShape, BoundaryMode, BoundaryShape, CapPolicy, CullCenter, CullRule, Importance,
+    Integrator, MergeCriterion, PhysicsObject, PhysicsSpace, SofteningKernel, Sph, TickReport,
+    ORIGIN_ADDED,
+};
+use plugin::{GasDrag, PointerAttractor};
+use rng::SimRng;
 use types::EuclideanSpace;
+use utils::LogLevel;
 use types::Field;
 use wasm_bindgen::prelude::*;
 
-use rand::os::OsRng;
-use rand::Rng;
+use rand::distributions::UnitCircle;
+use rand::seq::SliceRandom;
+use rand::{FromEntropy, Rng, SeedableRng};
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-impl Field for f64 {}
+impl Field for f64 {
+    fn is_finite(&self) -> bool {
+        f64::is_finite(*self)
+    }
+}
 
 #[wasm_bindgen]
 extern "C" {
+    #[cfg(feature = "demo")]
     fn alert(s: &str);
+
 }
 
+#[cfg(feature = "demo")]
+#[doc(hidden)]
 #[wasm_bindgen]
 pub fn greet() {
     alert("Hello, asdasdaswasm-generic-space!");
 }
 
+/// Runs once when the module is instantiated. Installs the panic hook, if that feature is
+/// enabled, so panics show up in the console, and turns on console warnings.
+#[wasm_bindgen(start)]
+pub fn init() {
+    utils::set_panic_hook();
+    utils::set_log_level(LogLevel::Warn);
+}
+
+/// Selects what the module writes to the console: `"off"`, `"warn"` for stability warnings
+/// (the default after `init`) or `"info"`, which adds notices like a lost reference
+/// particle. Returns false for unknown levels.
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> bool {
+    match LogLevel::parse(level) {
+        Some(level) => {
+            utils::set_log_level(level);
+            true
+        }
+        None => false,
+    }
+}
+
+#[wasm_bindgen]
+pub fn log_level() -> String {
+    utils::log_level().name().to_string()
+}
+
+/// What this build supports, as `{ simd: bool, threads: bool, version: string }`. A loader
+/// can use it to decide which bundle to fetch. The flags are fixed at compile time.
+#[wasm_bindgen]
+pub fn capabilities() -> JsValue {
+    let object = js_sys::Object::new();
+    let fields = [
+        ("simd", JsValue::from_bool(cfg!(target_feature = "simd128"))),
+        ("threads", JsValue::from_bool(cfg!(target_feature = "atomics"))),
+        ("version", JsValue::from_str(env!("CARGO_PKG_VERSION"))),
+    ];
+    for (key, value) in fields.iter() {
+        js_sys::Reflect::set(&object, &JsValue::from_str(key), value).unwrap();
+    }
+    object.into()
+}
+
+/// Optional parts compiled into this build, out of `"analysis"`, `"diagnostics"`,
+/// `"recording"` and `"snapshots"`. The methods of a missing part do not exist on `Universe`,
+/// so a wrapper can check here before calling them. Only these extras are optional: the
+/// simulation itself, with its editing, boundary, collision, SPH and cull methods, is in
+/// every build.
+#[wasm_bindgen]
+pub fn features() -> Vec<String> {
+    let features = [
+        ("analysis", cfg!(feature = "analysis")),
+        ("diagnostics", cfg!(feature = "diagnostics")),
+        ("recording", cfg!(feature = "recording")),
+        ("snapshots", cfg!(feature = "snapshots")),
+    ];
+    features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
 #[wasm_bindgen]
 pub struct VisibleUniverse {
     elems: Vec<[f64; 2]>,
@@ -38,6 +141,7 @@ pub struct Vector {
     value: [f64; 2],
 }
 #[wasm_bindgen]
+#[allow(non_snake_case)]
 impl Vector {
     pub fn getX(&self) -> f64 {
         self.value[0]
@@ -60,58 +164,1447 @@ impl VisibleUniverse {
         }
     }
 }
+/// Reference to one particle that survives reordering, obtained from `Universe::handle`.
+/// Every method looks the particle up by id in the given universe, and returns an error
+/// naming the id once it was culled or merged into another particle. Values are in the
+/// absolute frame, even with a reference particle set.
 #[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleHandle {
+    id: u32,
+}
+
+#[wasm_bindgen]
+impl ParticleHandle {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn is_alive(&self, universe: &Universe) -> bool {
+        universe.phys.index_of_id(self.id).is_some()
+    }
+
+    /// `[x, y]`
+    pub fn position(&self, universe: &Universe) -> Result<Vec<f64>, JsValue> {
+        Ok(universe.phys.elements[self.index(universe)?].position_vector.to_vec())
+    }
+
+    /// `[vx, vy]`
+    pub fn velocity(&self, universe: &Universe) -> Result<Vec<f64>, JsValue> {
+        Ok(universe.phys.elements[self.index(universe)?].direction_vector.to_vec())
+    }
+
+    pub fn mass(&self, universe: &Universe) -> Result<f64, JsValue> {
+        Ok(universe.phys.elements[self.index(universe)?].mass)
+    }
+
+    /// Returns an error if the particle is gone or a component is not finite.
+    pub fn set_velocity(&self, universe: &mut Universe, vx: f64, vy: f64) -> Result<(), JsValue> {
+        check_finite(&[("vx", vx), ("vy", vy)])?;
+        let i = self.index(universe)?;
+        universe.phys.elements[i].direction_vector = [vx, vy];
+        Ok(())
+    }
+}
+
+impl ParticleHandle {
+    fn index(&self, universe: &Universe) -> Result<usize, JsValue> {
+        universe.phys.index_of_id(self.id).ok_or_else(|| {
+            JsValue::from_str(&format!("particle {} was culled or merged", self.id))
+        })
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone)]
 pub struct Universe {
     phys: PhysicsSpace<f64, EuclideanSpace<f64>>,
+    /// Drawn from, in this order, by thermal noise, relaxation kicks, automatic splits and the
+    /// `random` population cap during a tick, and by `split_particle` and the JS helpers in
+    /// between.
+    rng: SimRng,
+    split_energy_fraction: f64,
+    auto_split: Option<(f64, u32)>,
+    cull_callback: Option<js_sys::Function>,
+    max_particles: Option<(usize, CapPolicy)>,
+    thermal_noise: f64,
+    relaxation_boost: f64,
+    pixels: Vec<u8>,
+    interleaved: Vec<f32>,
+    reference: Option<u32>, //Id of the particle outputs are relative to
+    reference_lost: bool,
+    export_channels: u32,
+    softening_ramp: Option<(f64, f64, u32, u64)>, //Initial, final, ticks and starting tick
+    gravity_ramp: Option<(f64, f64, u32, u64)>, //Initial, target, ticks and starting tick
+    #[cfg(feature = "analysis")]
+    probes: Vec<(u32, bounds::Bounds)>,
+    #[cfg(feature = "analysis")]
+    next_probe_id: u32,
+    cull_center: [f64; 2], //Used by radius rules while not following the center of mass
+    world_window: [f64; 4], //x, y, width and height mapped to [0,1]² by normalized positions
+    importance: Importance,
+    #[cfg(feature = "diagnostics")]
+    diagnostics: diagnostics::Diagnostics,
+    #[cfg(feature = "recording")]
+    recording: recording::Recording,
+    pointer: Option<PointerAttractor>,
+    pointer_smoothing: f64,
+    gas_drag: Option<(u32, GasDrag)>, //Plugin id and settings of the drag
+    #[cfg(feature = "scheduling")]
+    async_run: scheduling::AsyncSlot,
 }
 #[wasm_bindgen]
 impl Universe {
     pub fn new() -> Universe {
-        //let mut rng = rand::thread_rng();
+        let speed_range = 2.0;
+        let phys = UniverseBuilder::new()
+            .gravitational_constant(100f64)
+            .radius(3000f64)
+            .merge_distance(5f64)
+            .add_box(1000, [250.0, 250.0], [1200.0, 1200.0], speed_range, 0.0001)
+            .add_particle(800.0, 500.0, 0.0, 0.0, 1.0)
+            .build();
+        Universe::from_space(phys)
+    }
+
+    /// Builds a universe from a JSON scene description, for example
+    /// `{"seed": 1, "gravitational_constant": 100, "radius": 3000, "merge_distance": 5,
+    /// "components": [{"type": "disk", "count": 500, "center": [800, 800], "radius": 300,
+    /// "particle_mass": 0.0001, "central_mass": 1}]}`.
+    /// Component types are `particle` (`x`, `y`, `vx`, `vy`, `mass`), `box` (`count`, `min`,
+    /// `max`, `speed_range`, `particle_mass`), `disk` (`count`, `center`, `radius`,
+    /// `particle_mass`, `central_mass`, `velocity_dispersion`) and `plummer` (`count`, `center`,
+    /// `scale_radius`, `particle_mass`). Missing settings keep the defaults of `new`.
+    pub fn from_builder_json(json: &str) -> Result<Universe, JsValue> {
+        let scene = js_sys::JSON::parse(json)?;
+        let number = |object: &JsValue, key: &str| -> Result<Option<f64>, JsValue> {
+            let value = js_sys::Reflect::get(object, &JsValue::from_str(key))
+                .ok()
+                .and_then(|v| v.as_f64());
+            if let Some(v) = value {
+                check_finite(&[(key, v)])?;
+            }
+            Ok(value)
+        };
+        let require = |object: &JsValue, key: &str| {
+            number(object, key)?
+                .ok_or_else(|| JsValue::from_str(&format!("missing number {}", key)))
+        };
+        let point = |object: &JsValue, key: &str| -> Result<[f64; 2], JsValue> {
+            let value = js_sys::Reflect::get(object, &JsValue::from_str(key))?;
+            let array = js_sys::Array::from(&value);
+            match (array.get(0).as_f64(), array.get(1).as_f64()) {
+                (Some(x), Some(y)) => {
+                    check_finite(&[(key, x), (key, y)])?;
+                    Ok([x, y])
+                }
+                _ => Err(JsValue::from_str(&format!("{} must be [x, y]", key))),
+            }
+        };
+
+        let mut builder = UniverseBuilder::new();
+        if let Some(seed) = number(&scene, "seed")? {
+            builder = builder.seed(seed as u64);
+        }
+        if let Some(g) = number(&scene, "gravitational_constant")? {
+            builder = builder.gravitational_constant(g);
+        }
+        if let Some(radius) = number(&scene, "radius")? {
+            builder = builder.radius(radius);
+        }
+        if let Some(distance) = number(&scene, "merge_distance")? {
+            builder = builder.merge_distance(distance);
+        }
+        let components = js_sys::Reflect::get(&scene, &JsValue::from_str("components"))?;
+        for c in js_sys::Array::from(&components).iter() {
+            let kind = js_sys::Reflect::get(&c, &JsValue::from_str("type"))?.as_string();
+            builder = match kind.as_deref() {
+                Some("particle") => builder.add_particle(
+                    require(&c, "x")?,
+                    require(&c, "y")?,
+                    number(&c, "vx")?.unwrap_or(0.0),
+                    number(&c, "vy")?.unwrap_or(0.0),
+                    require(&c, "mass")?,
+                ),
+                Some("box") => builder.add_box(
+                    require(&c, "count")? as u32,
+                    point(&c, "min")?,
+                    point(&c, "max")?,
+                    number(&c, "speed_range")?.unwrap_or(0.0),
+                    require(&c, "particle_mass")?,
+                ),
+                Some("disk") => builder.add_disk(
+                    require(&c, "count")? as u32,
+                    point(&c, "center")?,
+                    require(&c, "radius")?,
+                    require(&c, "particle_mass")?,
+                    number(&c, "central_mass")?.unwrap_or(0.0),
+                    number(&c, "velocity_dispersion")?.unwrap_or(0.0),
+                ),
+                Some("plummer") => builder.add_plummer(
+                    require(&c, "count")? as u32,
+                    point(&c, "center")?,
+                    require(&c, "scale_radius")?,
+                    require(&c, "particle_mass")?,
+                ),
+                _ => return Err(JsValue::from_str("unknown component type")),
+            };
+        }
+        Ok(Universe::from_builder(&builder))
+    }
+
+    /// Cold collapse: `count` particles placed uniformly in a disk, all at rest and without
+    /// a central mass, so the whole disk falls inward and forms structure.
+    pub fn cold_collapse(
+        count: u32,
+        center_x: f64,
+        center_y: f64,
+        radius: f64,
+        particle_mass: f64,
+    ) -> Universe {
+        let phys = UniverseBuilder::new()
+            .add_cold_disk(count, [center_x, center_y], radius, particle_mass)
+            .build();
+        Universe::from_space(phys)
+    }
+
+    /// Creates a universe without any particles, to be filled with `add_particle`.
+    pub fn empty(gravitational_constant: f64, radius: f64, epsilon: f64) -> Universe {
+        Universe::with_elements(Vec::new(), gravitational_constant, radius, epsilon)
+    }
+
+    /// Returns the index of the new particle, or an error if any value is not finite.
+    pub fn add_particle(
+        &mut self,
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+        mass: f64,
+    ) -> Result<u32, JsValue> {
+        self.add_particle_with_origin(x, y, vx, vy, mass, ORIGIN_ADDED)
+    }
+
+    /// Like `add_particle`, but records `origin` instead of the `add_particle` code 1, e.g. to
+    /// tell the particles of different emitters apart. Codes 0 to 2 are used by the library.
+    pub fn add_particle_with_origin(
+        &mut self,
+        x: f64,
+        y: f64,
+        vx: f64,
+        vy: f64,
+        mass: f64,
+        origin: u16,
+    ) -> Result<u32, JsValue> {
+        check_finite(&[("x", x), ("y", y), ("vx", vx), ("vy", vy), ("mass", mass)])?;
+        let mut element = PhysicsObject::<f64>::new([x, y], [vx, vy], mass);
+        element.origin = origin;
+        Ok(self.phys.spawn(element) as u32)
+    }
 
-        let mut rng = OsRng::new().unwrap();;
+    pub fn particle_count(&self) -> u32 {
+        self.phys.elements.len() as u32
+    }
 
+    /// Positions as a flat `[x0, y0, x1, y1, ...]` array, relative to the reference particle
+    /// if one is set.
+    pub fn get_positions(&self) -> Vec<f64> {
+        let (origin, _) = self.reference_frame();
+        self.phys
+            .elements
+            .iter()
+            .flat_map(|e| vec![e.position_vector[0] - origin[0], e.position_vector[1] - origin[1]])
+            .collect()
+    }
 
-        let mut elems = Vec::new();
+    /// Like `get_positions`, but ordered by ascending mass, so drawing in this order puts the
+    /// heaviest particles on top. Particles of equal mass keep their order.
+    pub fn get_positions_sorted_by_mass(&self) -> Vec<f64> {
+        let (origin, _) = self.reference_frame();
+        let elements = &self.phys.elements;
+        let mut order: Vec<usize> = (0..elements.len()).collect();
+        order.sort_by(|&a, &b| {
+            elements[a]
+                .mass
+                .partial_cmp(&elements[b].mass)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        order
+            .into_iter()
+            .flat_map(|i| {
+                let p = &elements[i].position_vector;
+                vec![p[0] - origin[0], p[1] - origin[1]]
+            })
+            .collect()
+    }
+
+    /// Sets the region `get_positions_normalized` maps to the unit square, in the coordinates
+    /// of `get_positions`. Returns false and keeps the old window unless `w` and `h` are
+    /// positive.
+    pub fn set_world_window(&mut self, x: f64, y: f64, w: f64, h: f64) -> bool {
+        if !(w > 0.0 && h > 0.0) {
+            return false;
+        }
+        self.world_window = [x, y, w, h];
+        true
+    }
 
-    let speed_range = 2.0;
+    /// Positions like `get_positions`, with the world window mapped to `[0, 1]²`. Particles
+    /// outside the window get values outside that range. Without a window, this is the same
+    /// as `get_positions`.
+    pub fn get_positions_normalized(&self) -> Vec<f64> {
+        let [x, y, w, h] = self.world_window;
+        self.get_positions()
+            .chunks_exact(2)
+            .flat_map(|p| vec![(p[0] - x) / w, (p[1] - y) / h])
+            .collect()
+    }
 
-        for i in 0..1000 {
-            elems.push(PhysicsObject::<f64>::new(
-                [rng.gen_range(250.0, 1200.0), rng.gen_range(250.0, 1200.0)],
-                [rng.gen_range(-speed_range, speed_range), rng.gen_range(-speed_range, speed_range)],
-                //rng.gen_range(1.0, 2.0),
-                0.0001
-            ))
+    /// Square `[x, y, w, h]` around the center of mass that contains the nearest `percentile`
+    /// percent of the mass, in the coordinates of `get_positions`. Unlike the bounding box it
+    /// ignores a few far outliers, so it makes a steady world window. Empty without particles.
+    pub fn suggest_world_window(&self, percentile: f64) -> Vec<f64> {
+        if self.phys.elements.is_empty() {
+            return Vec::new();
         }
-        elems.push(PhysicsObject::<f64>::new(
-                [800.0, 500.0],
-                [0.0, 0.0],
-                1.0,
-            ));
+        let (origin, _) = self.reference_frame();
+        let center = self.phys.center_of_mass();
+        let r = self.phys.mass_radius(percentile.clamp(0.0, 100.0) / 100.0);
+        vec![center[0] - origin[0] - r, center[1] - origin[1] - r, 2.0 * r, 2.0 * r]
+    }
 
-        Universe {
-            phys: PhysicsSpace::new(
-                elems,
-                100f64,
-                EuclideanSpace::<f64> {
-                    field: std::marker::PhantomData::<f64>,
-                },
-                3000f64,
-                5f64,
-            ),
+    /// Selects the score `get_positions_by_importance` ranks particles by: `"momentum"` (the
+    /// default, speed times mass), `"speed"`, `"mass"` or `"acceleration"`. Returns false for
+    /// unknown scores.
+    pub fn set_importance(&mut self, kind: &str) -> bool {
+        self.importance = match kind {
+            "momentum" => Importance::Momentum,
+            "speed" => Importance::Speed,
+            "mass" => Importance::Mass,
+            "acceleration" => Importance::Acceleration,
+            _ => return false,
+        };
+        true
+    }
+
+    /// The `max_points` highest scoring particles as `[x0, y0, score0, x1, ...]`, highest score
+    /// first, positions like `get_positions`. Only the selected particles are sorted, so this
+    /// stays cheap for small `max_points`.
+    pub fn get_positions_by_importance(&self, max_points: u32) -> Vec<f64> {
+        let (origin, _) = self.reference_frame();
+        let scores = self.phys.importance(self.importance);
+        let by_score = |a: &usize, b: &usize| {
+            scores[*b]
+                .partial_cmp(&scores[*a])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        let k = (max_points as usize).min(order.len());
+        if k == 0 {
+            return Vec::new();
         }
+        order.select_nth_unstable_by(k - 1, by_score);
+        order.truncate(k);
+        order.sort_unstable_by(by_score);
+        order
+            .into_iter()
+            .flat_map(|i| {
+                let p = &self.phys.elements[i].position_vector;
+                vec![p[0] - origin[0], p[1] - origin[1], scores[i]]
+            })
+            .collect()
+    }
+
+    /// Accelerations as a flat `[ax0, ay0, ax1, ay1, ...]` array, as stored by the last tick
+    /// or `compute_forces_only`.
+    pub fn get_accelerations(&self) -> Vec<f64> {
+        self.phys
+            .elements
+            .iter()
+            .flat_map(|e| vec![e.acceleration_vector[0], e.acceleration_vector[1]])
+            .collect()
+    }
+
+    /// Recomputes every particle's acceleration for the current configuration without
+    /// integrating, for debugging the force field. Nothing moves and no time passes, so
+    /// calling it repeatedly gives the same field.
+    pub fn compute_forces_only(&mut self) {
+        self.phys.compute_accelerations();
+    }
+
+    /// Velocities as a flat `[vx0, vy0, vx1, vy1, ...]` array, relative to the reference
+    /// particle if one is set.
+    pub fn get_velocities(&self) -> Vec<f64> {
+        let (_, drift) = self.reference_frame();
+        self.phys
+            .elements
+            .iter()
+            .flat_map(|e| {
+                vec![e.direction_vector[0] - drift[0], e.direction_vector[1] - drift[1]]
+            })
+            .collect()
+    }
+
+    /// Bounding box `[x, y, width, height]` of all particles, relative to the reference
+    /// particle if one is set. Empty without particles.
+    pub fn get_bounds(&self) -> Vec<f64> {
+        let (origin, _) = self.reference_frame();
+        self.phys.bounds().map_or(Vec::new(), |b| {
+            vec![b.x - origin[0], b.y - origin[1], b.width, b.height]
+        })
+    }
+
+    /// Makes the outputs follow particle `index`, typically the central mass: positions,
+    /// velocities, bounds and the velocity grid are reported relative to it. The simulation
+    /// itself is unaffected. `None` or an invalid index goes back to absolute output.
+    pub fn set_reference_particle(&mut self, index: Option<u32>) {
+        self.reference = index
+            .and_then(|i| self.phys.elements.get(i as usize))
+            .map(|e| e.id);
+        self.reference_lost = false;
+    }
+
+    /// Whether the reference particle was culled or merged away, which clears the reference.
+    pub fn reference_lost(&self) -> bool {
+        self.reference_lost
+    }
+
+    pub fn get_masses(&self) -> Vec<f64> {
+        self.phys.elements.iter().map(|e| e.mass).collect()
+    }
+
+    /// Stable ids of all particles, in index order. Ids survive deletions and merges
+    /// of other particles and are never reused.
+    pub fn get_ids(&self) -> Vec<u32> {
+        self.phys.elements.iter().map(|e| e.id).collect()
+    }
+
+    /// Tick at which each particle was created, in index order. Merged particles keep the
+    /// earlier birth tick.
+    pub fn get_birth_ticks(&self) -> Vec<f64> {
+        self.phys.elements.iter().map(|e| e.birth_tick as f64).collect()
+    }
+
+    /// Origin code of each particle, in index order: 0 for the initial conditions, 1 for
+    /// `add_particle`, 2 for the new parts of a split, or the code given to
+    /// `add_particle_with_origin`. Merged particles keep the code of the survivor.
+    pub fn get_origins(&self) -> Vec<u16> {
+        self.phys.elements.iter().map(|e| e.origin).collect()
+    }
+
+    /// Current index of the particle with the given id, or -1 if it no longer exists.
+    pub fn index_of_id(&self, id: u32) -> i32 {
+        self.phys.index_of_id(id).map_or(-1, |i| i as i32)
+    }
+
+    /// Handle following particle `index` by its id, `None` if the index is out of range.
+    pub fn handle(&self, index: u32) -> Option<ParticleHandle> {
+        self.phys
+            .elements
+            .get(index as usize)
+            .map(|e| ParticleHandle { id: e.id })
+    }
+
+    /// Rasterizes the particles like `render_pixels` and returns a view of the image in
+    /// the module's memory for `putImageData`, without copying it. The view is only valid
+    /// until the next call into the module, so draw it right away.
+    pub fn render_to_buffer(
+        &mut self,
+        width: u32,
+        height: u32,
+        view_x: f64,
+        view_y: f64,
+        scale: f64,
+    ) -> js_sys::Uint8ClampedArray {
+        let pixels = self.render_pixels(width, height, view_x, view_y, scale);
+        // Safety: the view is handed straight to JS, which is told not to keep it across
+        // calls, so no allocation can move or free the pixels while it is in use.
+        unsafe { js_sys::Uint8ClampedArray::view(pixels) }
+    }
+
+    /// Selects the optional channels of `export_frame` and `get_interleaved`: 1 for
+    /// velocities, 2 for masses. Positions are always included.
+    pub fn set_export_channels(&mut self, flags: u32) {
+        self.export_channels = flags;
+    }
+
+    /// Number of values per particle in `get_interleaved`: 2 for the position, plus 2 with the
+    /// velocity channel and 1 with the mass channel.
+    pub fn interleaved_stride(&self) -> u32 {
+        let mut stride = 2;
+        if self.export_channels & frame::VELOCITIES != 0 {
+            stride += 2;
+        }
+        if self.export_channels & frame::MASSES != 0 {
+            stride += 1;
+        }
+        stride
+    }
+
+    /// All particles interleaved into one vertex buffer for a single upload: per particle
+    /// `x, y`, then `vx, vy` and `mass` if those export channels are selected, see
+    /// `interleaved_stride`. Positions and velocities are relative to the reference particle
+    /// like in `get_positions`. Returns a view of a buffer in the module's memory that is
+    /// reused between calls, so it is only valid until the next call into the module.
+    pub fn get_interleaved(&mut self) -> js_sys::Float32Array {
+        let values = self.interleaved();
+        // Safety: as in `render_to_buffer`, JS is told not to keep the view across calls,
+        // so no allocation can move or free the buffer while it is in use.
+        unsafe { js_sys::Float32Array::view(values) }
+    }
+
+    /// Moves every particle by `(dx, dy)` without changing velocities.
+    pub fn translate(&mut self, dx: f64, dy: f64) -> Result<(), JsValue> {
+        check_finite(&[("dx", dx), ("dy", dy)])?;
+        self.phys.translate(&[dx, dy]);
+        Ok(())
+    }
+
+    /// Rotates the whole system by `angle` radians about `(pivot_x, pivot_y)`.
+    /// Velocities are rotated as well.
+    pub fn rotate(&mut self, angle: f64, pivot_x: f64, pivot_y: f64) -> Result<(), JsValue> {
+        check_finite(&[("angle", angle), ("pivot_x", pivot_x), ("pivot_y", pivot_y)])?;
+        self.phys.rotate(angle, &[pivot_x, pivot_y]);
+        Ok(())
+    }
+
+    /// Pushes every particle away from `(center_x, center_y)` with an added speed of
+    /// `speed * exp(-falloff * r)`, like an explosion. A particle exactly at the center is
+    /// not pushed.
+    pub fn explode(
+        &mut self,
+        center_x: f64,
+        center_y: f64,
+        speed: f64,
+        falloff: f64,
+    ) -> Result<(), JsValue> {
+        check_finite(&[
+            ("center_x", center_x),
+            ("center_y", center_y),
+            ("speed", speed),
+            ("falloff", falloff),
+        ])?;
+        self.phys.explode(&[center_x, center_y], speed, falloff);
+        Ok(())
+    }
+
+    /// Protects particle `index` from all cull rules, e.g. to keep a central mass that drifted
+    /// beyond the radius. Merging keeps the protection. Returns false if the index is out of
+    /// range.
+    pub fn set_protected(&mut self, index: usize, protected: bool) -> bool {
+        match self.phys.elements.get_mut(index) {
+            Some(e) => {
+                e.protected = protected;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Changes the mass of particle `index`, taking effect in the next tick. Returns false if
+    /// the index is out of range, and an error if the mass is negative or not finite.
+    pub fn set_mass(&mut self, index: usize, mass: f64) -> Result<bool, JsValue> {
+        check_finite(&[("mass", mass)])?;
+        if mass < 0.0 {
+            return Err(JsValue::from_str(&format!("mass must not be negative, got {}", mass)));
+        }
+        Ok(match self.phys.elements.get_mut(index) {
+            Some(e) => {
+                e.mass = mass;
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Removes particle `index` in the removal pass of the next tick, together with the
+    /// particles culled there. Until then it stays in the getters but no longer merges or
+    /// attracts. Returns false if the index is out of range.
+    pub fn remove_particle(&mut self, index: usize) -> bool {
+        self.phys.delete(index)
+    }
+
+    /// Replaces particle `index` by `parts` particles of equal mass within `scatter_radius`.
+    /// Mass and momentum are conserved. Returns false if the index is out of range, and an
+    /// error if the radius is not finite.
+    pub fn split_particle(
+        &mut self,
+        index: usize,
+        parts: u32,
+        scatter_radius: f64,
+    ) -> Result<bool, JsValue> {
+        check_finite(&[("scatter_radius", scatter_radius)])?;
+        Ok(self.phys.split(
+            index,
+            parts as usize,
+            scatter_radius,
+            self.split_energy_fraction,
+            &mut self.rng,
+        ))
+    }
+
+    /// Reorders the particles randomly without changing them or their ids, to rule out
+    /// effects of the index order. Uses its own generator seeded with `seed`, so the same
+    /// seed gives the same order and the universe's generator is left alone.
+    pub fn shuffle(&mut self, seed: u64) {
+        self.phys.elements.shuffle(&mut SimRng::seed_from_u64(seed));
+    }
+
+    /// Reseeds the random number generator used by splitting, the population cap and thermal
+    /// noise, so runs from the same state are reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SimRng::seed_from_u64(seed);
+    }
+
+    /// State of the random number generator, to be restored with `set_rng_state`.
+    pub fn rng_state(&self) -> String {
+        format!("{:016x}", self.rng.state())
+    }
+
+    /// Restores a state saved by `rng_state`, so the following random operations repeat
+    /// exactly. Returns false and keeps the current state if `state` cannot be parsed.
+    pub fn set_rng_state(&mut self, state: &str) -> bool {
+        match u64::from_str_radix(state, 16) {
+            Ok(state) => {
+                self.rng = SimRng::from_state(state);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Uniform number in `[min, max)` from the universe's generator, so randomness in the
+    /// app can share the seeded stream.
+    pub fn random_f64(&mut self, min: f64, max: f64) -> f64 {
+        if min < max {
+            self.rng.gen_range(min, max)
+        } else {
+            min
+        }
+    }
+
+    /// Random direction `[x, y]` of length one from the universe's generator.
+    pub fn random_unit_vector(&mut self) -> Vec<f64> {
+        self.rng.sample(UnitCircle).to_vec()
+    }
+
+    /// Adds Gaussian velocity kicks with standard deviation `amplitude` to every particle
+    /// after each tick, like a heat bath. Zero disables the noise.
+    pub fn set_thermal_noise(&mut self, amplitude: f64) {
+        self.thermal_noise = amplitude;
+    }
+
+    /// Turns the relative velocity of every particle and its nearest neighbor by a random
+    /// angle after each tick, scaled by `coefficient` times the deflection of a gravitational
+    /// encounter at their separation. This restores some of the two-body relaxation lost to
+    /// merging. Momentum and energy are kept. Zero disables it.
+    pub fn set_relaxation_boost(&mut self, coefficient: f64) {
+        self.relaxation_boost = coefficient;
+    }
+
+    /// Drags particles between `r_min` and `r_max` from the cull center towards the local
+    /// circular velocity, with an acceleration of `strength` times the difference, like a
+    /// gas disk. Orbits become circular and energy is lost. `r_max` may be `Infinity`.
+    /// The center is taken at the start of every tick, so it follows the center of mass
+    /// after `set_cull_follow_com`. Zero strength removes the drag.
+    pub fn set_gas_drag(&mut self, strength: f64, r_min: f64, r_max: f64) {
+        if let Some((id, _)) = self.gas_drag.take() {
+            self.phys.remove_plugin(id);
+        }
+        if strength > 0.0 {
+            let drag = GasDrag {
+                strength,
+                center: self.phys.cull_origin(),
+                r_min,
+                r_max,
+            };
+            let id = self.phys.add_plugin(Box::new(drag.clone()));
+            self.gas_drag = Some((id, drag));
+        }
+    }
+
+    /// Places an attracting point mass of `mass` at the pointer, softened by the merge
+    /// distance. Call it once per frame with the latest cursor sample. During the next tick
+    /// the pointer moves linearly from where it was to the new sample, so fast cursor motion
+    /// does not give particles jerky kicks. Returns an error for non-finite arguments.
+    pub fn set_pointer(&mut self, x: f64, y: f64, mass: f64) -> Result<(), JsValue> {
+        check_finite(&[("x", x), ("y", y), ("mass", mass)])?;
+        let time = self.phys.tick_count() as f64;
+        let (from, to) = match &self.pointer {
+            Some(pointer) => {
+                let (last, a) = (pointer.to, self.pointer_smoothing);
+                (pointer.position(time), [last[0] + a * (x - last[0]), last[1] + a * (y - last[1])])
+            }
+            None => ([x, y], [x, y]),
+        };
+        self.pointer = Some(PointerAttractor {
+            strength: self.phys.gravitational_constant() * mass,
+            softening: self.phys.epsilon(),
+            from,
+            to,
+            start: time,
+        });
+        self.update_external_forces();
+        Ok(())
+    }
+
+    pub fn clear_pointer(&mut self) {
+        self.pointer = None;
+        self.update_external_forces();
+    }
+
+    /// Moves the pointer only by `alpha` times the distance to each new sample, a moving
+    /// average that calms a shaky cursor. 1, the default, follows the samples exactly and 0
+    /// keeps the pointer in place. Returns an error for a non-finite `alpha`.
+    pub fn set_pointer_position_smoothing(&mut self, alpha: f64) -> Result<(), JsValue> {
+        check_finite(&[("alpha", alpha)])?;
+        self.pointer_smoothing = alpha.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    /// `[x, y]` of the pointer at the current simulation time, empty without a pointer.
+    pub fn pointer_position(&self) -> Vec<f64> {
+        let time = self.phys.tick_count() as f64;
+        self.pointer.iter().flat_map(|p| p.position(time).to_vec()).collect()
+    }
+
+    /// Adds Gaussian random velocities with standard deviation `sigma` per component to all
+    /// particles once, minus their mass-weighted mean so the center of mass keeps its velocity.
+    pub fn heat(&mut self, sigma: f64) {
+        self.phys.heat(sigma, &mut self.rng);
+    }
+
+    /// Fraction of the parent's binding energy given to the parts of a split as random motion.
+    pub fn set_split_energy_fraction(&mut self, fraction: f64) {
+        self.split_energy_fraction = fraction;
     }
 
-    pub fn tick(&mut self) -> VisibleUniverse {
+    /// Splits every particle heavier than `threshold` into `parts` particles after each tick.
+    /// The parts are scattered over twice the merge distance times `sqrt(parts)`, parts that
+    /// still end up closer than the merge distance are merged again on the next tick.
+    /// A threshold of zero or less disables automatic splitting.
+    pub fn auto_split_above_mass(&mut self, threshold: f64, parts: u32) {
+        self.auto_split = if threshold > 0.0 && parts >= 2 {
+            Some((threshold, parts))
+        } else {
+            None
+        };
+    }
+
+    /// Selects how particles are advanced each tick: `"hybrid"` (the default) or
+    /// `"kick_drift_kick"`, a symplectic leapfrog that conserves energy better over long runs.
+    /// Returns false for unknown integrators.
+    pub fn set_integrator(&mut self, kind: &str) -> bool {
+        let integrator = match kind {
+            "hybrid" => Integrator::Hybrid,
+            "kick_drift_kick" => Integrator::KickDriftKick,
+            _ => return false,
+        };
+        self.phys.set_integrator(integrator);
+        true
+    }
+
+    /// Selects when particles closer than the merge distance are merged: `"distance"` (the
+    /// default) always merges them, `"bound"` only if they are gravitationally bound, so
+    /// fast flybys pass through each other. Returns false for unknown criteria.
+    pub fn set_merge_criterion(&mut self, kind: &str) -> bool {
+        let criterion = match kind {
+            "distance" => MergeCriterion::Distance,
+            "bound" => MergeCriterion::Bound,
+            _ => return false,
+        };
+        self.phys.set_merge_criterion(criterion);
+        true
+    }
+
+    /// Removes particles whose position or velocity became NaN or infinite during a tick,
+    /// instead of letting them spread NaN to every other particle. On by default in debug
+    /// builds.
+    pub fn set_nan_guard(&mut self, enabled: bool) {
+        self.phys.set_nan_guard(enabled);
+    }
+
+    /// Id of the first particle removed by the NaN guard during the last tick that removed
+    /// any, `None` if it never did.
+    pub fn last_nan_report(&self) -> Option<u32> {
+        self.phys.last_nan_report()
+    }
+
+    /// Adds a rule to the removal pass of each tick. `kind` is one of `"radius"`,
+    /// `"min_speed"`, `"max_speed"`, `"min_mass"` or `"max_age"` in ticks. Returns false for
+    /// unknown kinds and an error if `value` is not finite. By default a single radius rule is
+    /// active. A `max_age` rule works like `set_max_age`: it replaces any earlier one, 0
+    /// removes the limit, and other values must be whole and not negative.
+    pub fn add_cull_rule(&mut self, kind: &str, value: f64) -> Result<bool, JsValue> {
+        check_finite(&[("value", value)])?;
+        let rule = match kind {
+            "radius" => CullRule::Radius(value),
+            "min_speed" => CullRule::MinSpeed(value),
+            "max_speed" => CullRule::MaxSpeed(value),
+            "min_mass" => CullRule::MinMass(value),
+            "max_age" => {
+                if value < 0.0 || value.fract() != 0.0 {
+                    return Err(JsValue::from_str(&format!(
+                        "max_age must be a whole number of ticks, got {}",
+                        value
+                    )));
+                }
+                let ticks = value as u64;
+                self.phys.set_max_age(if ticks > 0 { Some(ticks) } else { None });
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        self.phys.add_cull_rule(rule);
+        Ok(true)
+    }
+
+    pub fn gravitational_constant(&self) -> f64 {
+        self.phys.gravitational_constant()
+    }
+
+    /// Changes the gravitational constant at once and stops a running gravity ramp.
+    pub fn set_gravitational_constant(&mut self, g: f64) {
+        self.gravity_ramp = None;
+        self.phys.set_gravitational_constant(g);
+    }
+
+    /// Changes the gravitational constant linearly from its current value to `target_g` over
+    /// the next `ticks` ticks. For a soft start, set it to zero first; ramping to zero stops
+    /// gravity softly.
+    pub fn ramp_gravity(&mut self, target_g: f64, ticks: u32) {
+        let current = self.phys.gravitational_constant();
+        self.gravity_ramp = Some((current, target_g, ticks, self.phys.tick_count()));
+        self.update_gravity();
+    }
+
+    /// Starts the force softening at `initial` and lowers it to `final_softening` over the
+    /// next `over_ticks` ticks, after which it stays there. The squared softening is
+    /// interpolated geometrically, or linearly if either end is zero. A large initial value
+    /// keeps overlapping particles of a cold start from exploding.
+    pub fn set_softening_ramp(&mut self, initial: f64, final_softening: f64, over_ticks: u32) {
+        self.softening_ramp = Some((initial, final_softening, over_ticks, self.phys.tick_count()));
+        self.update_softening();
+    }
+
+    /// Selects how forces are softened within the softening length: `"plummer"` (the
+    /// default) or `"cubic_spline"`, which is exactly Newtonian beyond the softening length.
+    /// Returns false for unknown kernels.
+    pub fn set_softening_kernel(&mut self, kind: &str) -> bool {
+        let kernel = match kind {
+            "plummer" => SofteningKernel::Plummer,
+            "cubic_spline" => SofteningKernel::CubicSpline,
+            _ => return false,
+        };
+        self.phys.set_softening_kernel(kernel);
+        true
+    }
+
+    /// Softening length currently used by the force law, zero unless a ramp was set.
+    pub fn softening(&self) -> f64 {
+        self.phys.softening()
+    }
+
+    /// Selects what happens to particles beyond the radius rules or the rectangle bounds:
+    /// `"delete"` (the default) removes them, `"clamp"` puts them back onto the boundary and
+    /// zeros their velocity across it, keeping the part along it. Returns false for unknown
+    /// modes.
+    pub fn set_boundary_mode(&mut self, mode: &str) -> bool {
+        let mode = match mode {
+            "delete" => BoundaryMode::Delete,
+            "clamp" => BoundaryMode::Clamp,
+            _ => return false,
+        };
+        self.phys.set_boundary_mode(mode);
+        true
+    }
+
+    /// Removes particles older than `ticks` ticks, replacing any earlier `max_age` rule,
+    /// also one from `add_cull_rule`. 0 removes the limit.
+    pub fn set_max_age(&mut self, ticks: u32) {
+        self.phys
+            .set_max_age(if ticks > 0 { Some(u64::from(ticks)) } else { None });
+    }
+
+    /// Measures radius rules from `(x, y)` instead of the origin, and stops following the
+    /// center of mass.
+    pub fn set_cull_center(&mut self, x: f64, y: f64) -> Result<(), JsValue> {
+        check_finite(&[("x", x), ("y", y)])?;
+        self.cull_center = [x, y];
+        self.phys.set_cull_center(CullCenter::Fixed(self.cull_center));
+        Ok(())
+    }
+
+    /// Measures radius rules from the center of mass at the start of every tick while
+    /// `follow` is true. Otherwise the center set by `set_cull_center` is used again.
+    pub fn set_cull_follow_com(&mut self, follow: bool) {
+        self.phys.set_cull_center(if follow {
+            CullCenter::CenterOfMass
+        } else {
+            CullCenter::Fixed(self.cull_center)
+        });
+    }
+
+    /// Keeps particles in the rectangle from `(min_x, min_y)` to `(max_x, max_y)` instead of
+    /// the radius rules. The boundary mode decides whether leaving particles are deleted or
+    /// clamped.
+    pub fn set_rect_bounds(
+        &mut self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    ) -> Result<(), JsValue> {
+        check_finite(&[("min_x", min_x), ("min_y", min_y), ("max_x", max_x), ("max_y", max_y)])?;
+        self.phys.set_boundary_shape(BoundaryShape::Rect {
+            min: [min_x, min_y],
+            max: [max_x, max_y],
+        });
+        Ok(())
+    }
+
+    /// Goes back to the circular radius rules after `set_rect_bounds`.
+    pub fn clear_rect_bounds(&mut self) {
+        self.phys.set_boundary_shape(BoundaryShape::Circle);
+    }
+
+    /// Removes all cull rules, including the default radius rule.
+    pub fn clear_cull_rules(&mut self) {
+        self.phys.clear_cull_rules();
+    }
+
+    /// Adds a static rectangle with lower corner `(x, y)` that deletes every particle
+    /// entering it. Returns its id.
+    pub fn add_absorber_rect(&mut self, x: f64, y: f64, w: f64, h: f64) -> Result<u32, JsValue> {
+        check_finite(&[("x", x), ("y", y), ("w", w), ("h", h)])?;
+        Ok(self.phys.add_absorber(AbsorberShape::Rect {
+            min: [x, y],
+            max: [x + w, y + h],
+        }))
+    }
+
+    /// Adds a static circle that deletes every particle entering it. Returns its id.
+    pub fn add_absorber_circle(&mut self, x: f64, y: f64, r: f64) -> Result<u32, JsValue> {
+        check_finite(&[("x", x), ("y", y), ("r", r)])?;
+        Ok(self.phys.add_absorber(AbsorberShape::Circle {
+            center: [x, y],
+            radius: r,
+        }))
+    }
+
+    pub fn remove_absorber(&mut self, id: u32) -> bool {
+        self.phys.remove_absorber(id)
+    }
+
+    /// Number of particles swallowed by absorber `id`, zero for unknown ids.
+    pub fn absorbed_count(&self, id: u32) -> u32 {
+        self.phys.absorber(id).map_or(0, |a| a.absorbed_count as u32)
+    }
+
+    /// Total mass swallowed by absorber `id`, zero for unknown ids.
+    pub fn absorbed_mass(&self, id: u32) -> f64 {
+        self.phys.absorber(id).map_or(0.0, |a| a.absorbed_mass)
+    }
+
+    /// Makes particle `index`, typically a black hole, swallow every particle whose path
+    /// during a tick passes within `radius` of it, so fast particles cannot step across it
+    /// between ticks. A radius of zero or less turns this off again. Returns false if the
+    /// index is out of range.
+    pub fn set_accretion_radius(&mut self, index: usize, radius: f64) -> bool {
+        match self.phys.elements.get(index) {
+            Some(e) => {
+                let id = e.id;
+                self.phys.set_accretor(id, Some(radius).filter(|&r| r > 0.0));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Damps the relative velocity of pairs closer than `separation` by `rate` each tick,
+    /// making close binaries spiral together. A rate of zero disables the effect.
+    pub fn set_binary_inspiral(&mut self, rate: f64, separation: f64) {
+        self.phys.set_binary_inspiral(rate, separation);
+    }
+
+    /// Integrates pairs closer than `distance` as isolated two-body orbits with `substeps`
+    /// substeps per tick, which keeps tight binaries stable without shrinking the global
+    /// timestep.
+    pub fn set_binary_regularization(&mut self, enabled: bool, substeps: u32, distance: f64) {
+        self.phys.set_binary_regularization(if enabled && substeps > 0 {
+            Some((substeps as usize, distance))
+        } else {
+            None
+        });
+    }
+
+    /// Number of pairs regularized during the last tick.
+    pub fn regularized_pairs(&self) -> u32 {
+        self.phys.regularized_pairs() as u32
+    }
+
+    /// Disabling gravity skips the force computation, particles then move in straight lines.
+    pub fn set_gravity_enabled(&mut self, enabled: bool) {
+        self.phys.set_gravity_enabled(enabled);
+    }
+
+    /// Disabling self-gravity keeps only the pull of particles heavier than
+    /// `set_heavy_threshold`, so light particles move as test masses around the heavy ones
+    /// and ignore each other. The force sum then only runs over the heavy particles, see
+    /// `force_sources` in the tick report. On by default.
+    pub fn set_self_gravity(&mut self, enabled: bool) {
+        self.phys.set_self_gravity(enabled);
+    }
+
+    /// Particles heavier than `mass` attract the others even without self-gravity. Zero by
+    /// default.
+    pub fn set_heavy_threshold(&mut self, mass: f64) {
+        self.phys.set_heavy_threshold(mass);
+    }
+
+    /// Makes particles behave like a soft fluid: neighbors within `smoothing_radius` push
+    /// each other towards the mean density the particles had when this was enabled, with
+    /// `stiffness` scaling the pressure and `viscosity` damping their relative velocity.
+    /// Gravity still applies unless disabled.
+    pub fn set_sph(
+        &mut self,
+        enabled: bool,
+        smoothing_radius: f64,
+        stiffness: f64,
+        viscosity: f64,
+    ) {
+        if !enabled || smoothing_radius <= 0.0 {
+            self.phys.set_sph(None);
+            return;
+        }
+        let mut sph = Sph {
+            smoothing_radius,
+            stiffness,
+            rest_density: 0.0,
+            viscosity,
+        };
+        self.phys.set_sph(Some(sph.clone()));
+        let densities = self.phys.densities();
+        if !densities.is_empty() {
+            sph.rest_density = densities.iter().sum::<f64>() / densities.len() as f64;
+        }
+        self.phys.set_sph(Some(sph));
+    }
+
+    /// Kernel-weighted density around every particle, empty while SPH is disabled.
+    pub fn get_densities(&self) -> Vec<f64> {
+        self.phys.densities()
+    }
+
+    /// Kinetic plus potential energy. The potential is summed over all pairs, so this is
+    /// O(n²) and best not called every frame for large universes.
+    pub fn total_energy(&self) -> f64 {
+        self.phys.total_energy()
+    }
+
+    /// Ticks computed so far, one tick being one time unit.
+    pub fn simulation_time(&self) -> f64 {
+        self.phys.tick_count() as f64
+    }
+
+    /// Caps the number of particles at `n`, applied after every tick. `policy` selects which
+    /// particles are removed: `"oldest"`, `"lightest"`, `"random"` or `"merge_lightest"`,
+    /// which folds the removed mass into the nearest remaining particle. A cap of zero
+    /// disables the limit. Returns false for unknown policies.
+    pub fn set_max_particles(&mut self, n: u32, policy: &str) -> bool {
+        let policy = match policy {
+            "oldest" => CapPolicy::Oldest,
+            "lightest" => CapPolicy::Lightest,
+            "random" => CapPolicy::Random,
+            "merge_lightest" => CapPolicy::MergeLightest,
+            _ => return false,
+        };
+        self.max_particles = if n > 0 {
+            Some((n as usize, policy))
+        } else {
+            None
+        };
+        true
+    }
+
+    /// Calls `f` once per tick with a Float64Array of `[x, y, vx, vy, mass]` per particle.
+    /// `f` returns an array of indices to remove. This crosses the JS boundary every tick
+    /// and copies all particles, so it is slow for large universes. If `f` throws or returns
+    /// anything but indices of existing particles, `tick` fails and removes nothing.
+    /// Protected particles are kept even if `f` returns them.
+    pub fn set_cull_callback(&mut self, f: &js_sys::Function) {
+        self.cull_callback = Some(f.clone());
+    }
+
+    pub fn clear_cull_callback(&mut self) {
+        self.cull_callback = None;
+    }
+
+    /// Runs `steps` ticks and returns the simulation time. For long fast-forwards, use
+    /// `tick_async`, which keeps the page responsive. Stops at the first tick that fails, see
+    /// `tick`.
+    pub fn tick_n(&mut self, steps: u32) -> Result<f64, JsValue> {
+        for _ in 0..steps {
+            self.tick()?;
+        }
+        Ok(self.simulation_time())
+    }
+
+    /// Ticks and reports what changed during the tick.
+    pub fn tick_report(&mut self) -> Result<TickReport, JsValue> {
+        self.tick()?;
+        Ok(*self.phys.report())
+    }
+
+    /// Advances the simulation by one tick. Fails without changing anything if the cull
+    /// callback throws or returns something other than indices of existing particles.
+    pub fn tick(&mut self) -> Result<VisibleUniverse, JsValue> {
+        self.run_cull_callback()?;
+        Ok(self.step())
+    }
+}
+
+impl Universe {
+    /// Rasterizes the particles as opaque black pixels on a transparent `width` x `height`
+    /// RGBA image. `(view_x, view_y)` is the world position of the top left corner and
+    /// `scale` the number of pixels per world unit. The image is drawn into a buffer reused
+    /// between calls.
+    pub fn render_pixels(
+        &mut self,
+        width: u32,
+        height: u32,
+        view_x: f64,
+        view_y: f64,
+        scale: f64,
+    ) -> &[u8] {
+        let (width, height) = (width as usize, height as usize);
+        self.pixels.clear();
+        self.pixels.resize(width * height * 4, 0);
+        for e in self.phys.elements.iter() {
+            let x = ((e.position_vector[0] - view_x) * scale).floor();
+            let y = ((e.position_vector[1] - view_y) * scale).floor();
+            if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+                let offset = (y as usize * width + x as usize) * 4;
+                self.pixels[offset + 3] = 255;
+            }
+        }
+        &self.pixels
+    }
+
+    fn with_elements(
+        elems: Vec<PhysicsObject<f64>>,
+        gravitational_constant: f64,
+        radius: f64,
+        epsilon: f64,
+    ) -> Universe {
+        Universe::from_space(PhysicsSpace::new(
+            elems,
+            gravitational_constant,
+            EuclideanSpace::<f64> {
+                field: std::marker::PhantomData::<f64>,
+            },
+            radius,
+            epsilon,
+        ))
+    }
+
+    /// Sets the softening the ramp prescribes for the next tick and ends the ramp once it
+    /// reached its final value.
+    fn update_softening(&mut self) {
+        if let Some((initial, last, ticks, start)) = self.softening_ramp {
+            let elapsed = (self.phys.tick_count() - start) as f64;
+            let t = if ticks == 0 { 1.0 } else { (elapsed / f64::from(ticks)).min(1.0) };
+            let (a, b) = (initial * initial, last * last);
+            let squared = if a > 0.0 && b > 0.0 {
+                a * (b / a).powf(t)
+            } else {
+                a + (b - a) * t
+            };
+            self.phys.set_softening(squared.sqrt());
+            if t >= 1.0 {
+                self.phys.set_softening(last);
+                self.softening_ramp = None;
+            }
+        }
+    }
+
+    /// Moves the gas drag to the cull center at the start of the coming tick.
+    fn update_gas_drag(&mut self) {
+        if let Some((id, drag)) = &mut self.gas_drag {
+            drag.center = self.phys.cull_origin();
+            self.phys.replace_plugin(*id, Box::new(drag.clone()));
+        }
+    }
+
+    /// Removes the unprotected particles the cull callback returns, after checking that every
+    /// entry is the index of an existing particle.
+    fn run_cull_callback(&mut self) -> Result<(), JsValue> {
+        let f = match &self.cull_callback {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+        let data: Vec<f64> = self
+            .phys
+            .elements
+            .iter()
+            .flat_map(|e| {
+                vec![
+                    e.position_vector[0],
+                    e.position_vector[1],
+                    e.direction_vector[0],
+                    e.direction_vector[1],
+                    e.mass,
+                ]
+            })
+            .collect();
+        let data = js_sys::Float64Array::from(&data[..]);
+        let result = f.call1(&JsValue::NULL, &data)?;
+        let count = self.phys.elements.len();
+        let mut indices = Vec::new();
+        for value in js_sys::Array::from(&result).iter() {
+            match value.as_f64() {
+                Some(i) if i >= 0.0 && i.fract() == 0.0 && i < count as f64 => {
+                    indices.push(i as usize)
+                }
+                _ => {
+                    return Err(JsValue::from_str(&format!(
+                        "cull callback must return indices below {}, got {:?}",
+                        count, value
+                    )))
+                }
+            }
+        }
+        for i in indices {
+            if !self.phys.elements[i].protected {
+                self.phys.delete(i);
+            }
+        }
+        Ok(())
+    }
+
+    /// One tick of the simulation and the bookkeeping around it, without the cull callback.
+    fn step(&mut self) -> VisibleUniverse {
+        #[cfg(feature = "diagnostics")]
+        let started = diagnostics::clock_ms();
+        self.update_gas_drag();
         self.phys.tick();
+        self.update_softening();
+        self.update_gravity();
+        #[cfg(feature = "diagnostics")]
+        self.check_stability();
+        if self.thermal_noise > 0.0 {
+            self.phys.add_thermal_noise(self.thermal_noise, &mut self.rng);
+        }
+        if self.relaxation_boost > 0.0 {
+            self.phys.scatter(self.relaxation_boost, &mut self.rng);
+        }
+        if let Some((threshold, parts)) = self.auto_split {
+            let scatter_radius = 2.0 * self.phys.epsilon() * f64::from(parts).sqrt();
+            for i in 0..self.phys.elements.len() {
+                if self.phys.elements[i].mass > threshold {
+                    self.phys.split(
+                        i,
+                        parts as usize,
+                        scatter_radius,
+                        self.split_energy_fraction,
+                        &mut self.rng,
+                    );
+                }
+            }
+        }
+        if let Some((max, policy)) = self.max_particles {
+            self.phys.enforce_max_elements(max, policy, &mut self.rng);
+        }
+        self.phys.close_report();
+        if let Some(id) = self.reference {
+            if self.phys.index_of_id(id).is_none() {
+                self.reference = None;
+                self.reference_lost = true;
+                let notice = format!("reference particle {} is gone, outputs are absolute", id);
+                utils::log(LogLevel::Info, &notice);
+            }
+        }
+        #[cfg(feature = "recording")]
+        self.record_tick();
+        #[cfg(feature = "diagnostics")]
+        self.record_tick_time(started);
+        let (origin, _) = self.reference_frame();
         VisibleUniverse {
             elems: self
                 .phys
                 .elements
                 .iter()
-                .map(|e| e.position_vector)
+                .map(|e| [e.position_vector[0] - origin[0], e.position_vector[1] - origin[1]])
                 .collect(),
         }
     }
+
+    fn update_external_forces(&mut self) {
+        self.phys.clear_external_forces();
+        if let Some(pointer) = &self.pointer {
+            self.phys.add_external_force(Box::new(pointer.clone()));
+        }
+    }
+
+    fn update_gravity(&mut self) {
+        if let Some((initial, target, ticks, start)) = self.gravity_ramp {
+            let elapsed = (self.phys.tick_count() - start) as f64;
+            if ticks == 0 || elapsed >= f64::from(ticks) {
+                self.phys.set_gravitational_constant(target);
+                self.gravity_ramp = None;
+            } else {
+                let t = elapsed / f64::from(ticks);
+                self.phys.set_gravitational_constant(initial + (target - initial) * t);
+            }
+        }
+    }
+
+    /// Creates a universe from the particles and settings of `builder`, so Rust code can use
+    /// a seeded builder where JS would call `from_builder_json`.
+    pub fn from_builder(builder: &UniverseBuilder) -> Universe {
+        Universe::from_space(builder.build())
+    }
+
+    /// Fills the buffer behind `get_interleaved` and returns it.
+    pub fn interleaved(&mut self) -> &[f32] {
+        let (origin, drift) = self.reference_frame();
+        let channels = self.export_channels;
+        self.interleaved.clear();
+        for e in self.phys.elements.iter() {
+            let (p, v) = (&e.position_vector, &e.direction_vector);
+            self.interleaved.push((p[0] - origin[0]) as f32);
+            self.interleaved.push((p[1] - origin[1]) as f32);
+            if channels & frame::VELOCITIES != 0 {
+                self.interleaved.push((v[0] - drift[0]) as f32);
+                self.interleaved.push((v[1] - drift[1]) as f32);
+            }
+            if channels & frame::MASSES != 0 {
+                self.interleaved.push(e.mass as f32);
+            }
+        }
+        &self.interleaved
+    }
+
+    /// Position and velocity of the reference particle, zero without one.
+    fn reference_frame(&self) -> ([f64; 2], [f64; 2]) {
+        self.reference
+            .and_then(|id| self.phys.index_of_id(id))
+            .map_or(([0.0, 0.0], [0.0, 0.0]), |i| {
+                let e = &self.phys.elements[i];
+                (e.position_vector, e.direction_vector)
+            })
+    }
+
+    fn from_space(phys: PhysicsSpace<f64, EuclideanSpace<f64>>) -> Universe {
+        Universe {
+            phys,
+            rng: SimRng::from_entropy(),
+            split_energy_fraction: 0.1,
+            auto_split: None,
+            cull_callback: None,
+            max_particles: None,
+            thermal_noise: 0.0,
+            relaxation_boost: 0.0,
+            pixels: Vec::new(),
+            interleaved: Vec::new(),
+            reference: None,
+            reference_lost: false,
+            export_channels: 0,
+            softening_ramp: None,
+            gravity_ramp: None,
+            #[cfg(feature = "analysis")]
+            probes: Vec::new(),
+            #[cfg(feature = "analysis")]
+            next_probe_id: 0,
+            cull_center: [0.0, 0.0],
+            world_window: [0.0, 0.0, 1.0, 1.0],
+            importance: Importance::Momentum,
+            #[cfg(feature = "diagnostics")]
+            diagnostics: Default::default(),
+            #[cfg(feature = "recording")]
+            recording: Default::default(),
+            pointer: None,
+            pointer_smoothing: 1.0,
+            gas_drag: None,
+            #[cfg(feature = "scheduling")]
+            async_run: Default::default(),
+        }
+    }
+}
+
+/// Error naming the first value that is NaN or infinite.
+fn check_finite(values: &[(&str, f64)]) -> Result<(), JsValue> {
+    match values.iter().find(|(_, v)| !v.is_finite()) {
+        Some((name, v)) => Err(JsValue::from_str(&format!("{} must be finite, got {}", name, v))),
+        None => Ok(()),
+    }
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Several universes ticked together from a single call, e.g. to compare settings side by
+/// side. Adding a universe moves it into the set; it is then reached through its id.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct UniverseSet {
+    universes: Vec<(u32, Universe)>,
+    next_id: u32,
+}
+
+#[wasm_bindgen]
+impl UniverseSet {
+    pub fn new() -> UniverseSet {
+        UniverseSet::default()
+    }
+
+    /// Takes ownership of `universe` and returns its id, which is never reused. A universe
+    /// with a running `tick_async` is refused and freed, which rejects the run, as moving it
+    /// would leave the run ticking a stale copy.
+    pub fn add(&mut self, universe: Universe) -> Result<u32, JsValue> {
+        #[cfg(feature = "scheduling")]
+        {
+            if universe.async_run.active() {
+                return Err(JsValue::from_str("cannot add a universe while tick_async runs"));
+            }
+        }
+        self.next_id += 1;
+        self.universes.push((self.next_id - 1, universe));
+        Ok(self.next_id - 1)
+    }
+
+    /// Returns false if no universe has this id.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.universes.len();
+        self.universes.retain(|(i, _)| *i != id);
+        self.universes.len() < len
+    }
+
+    pub fn len(&self) -> u32 {
+        self.universes.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.universes.is_empty()
+    }
+
+    /// Ticks every universe once, in the order they were added. Stops at the first universe
+    /// whose tick fails.
+    pub fn tick_all(&mut self) -> Result<(), JsValue> {
+        for (_, universe) in self.universes.iter_mut() {
+            universe.tick()?;
+        }
+        Ok(())
+    }
+
+    /// `get_positions` of the universe with this id, empty if there is none.
+    pub fn get_positions(&self, id: u32) -> Vec<f64> {
+        self.universes
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map_or_else(Vec::new, |(_, universe)| universe.get_positions())
+    }
 }
@@ -1,6 +1,11 @@
+use crate::bounds::Bounds;
+use crate::plugin::{DirectNeighbors, ExternalForce, ForcePlugin};
 use crate::types::Field;
 use crate::types::MathSpace;
-use std::ops::{Add, Mul, Sub};
+use rand::distributions::{StandardNormal, UnitCircle};
+use rand::Rng;
+use std::collections::HashMap;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
 #[derive(Debug, Clone)]
@@ -9,11 +14,195 @@ pub struct PhysicsObject<K: Field> {
     pub direction_vector: [K; 2],
     pub acceleration_vector: [K; 2],
     pub mass: K,
+    /// Stable identifier, never reused within a `PhysicsSpace`
+    pub id: u32,
+    pub birth_tick: u64,
+    /// Where the element came from, one of the `ORIGIN_` codes or a code chosen by the caller
+    pub origin: u16,
+    /// Never removed by cull rules
+    pub protected: bool,
     status: ObjectStatus,
 }
 
+/// Origin of elements of the initial conditions
+pub const ORIGIN_INITIAL: u16 = 0;
+/// Origin of elements added after the start without an own code
+pub const ORIGIN_ADDED: u16 = 1;
+/// Origin of the new parts of a split element. The part keeping the id keeps its origin.
+pub const ORIGIN_SPLIT: u16 = 2;
+
+/// Reason to remove an element during the tick's removal pass.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CullRule<K: Field> {
+    /// Remove elements further than this away from the cull center
+    Radius(K),
+    /// Remove elements slower than this
+    MinSpeed(K),
+    /// Remove elements faster than this
+    MaxSpeed(K),
+    /// Remove elements lighter than this
+    MinMass(K),
+    /// Remove elements that have existed for more than this many ticks
+    MaxAge(u64),
+}
+
+/// Point `CullRule::Radius` measures distances from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CullCenter<K: Field> {
+    Fixed([K; 2]),
+    /// The center of mass at the start of each tick
+    CenterOfMass,
+}
+
+/// Shape of the region elements are kept in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoundaryShape<K: Field> {
+    /// The circles of the `CullRule::Radius` rules
+    Circle,
+    /// Box from `min` to `max`, radius rules are ignored
+    Rect { min: [K; 2], max: [K; 2] },
+}
+
+/// What happens to elements leaving the boundary shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundaryMode {
+    /// Remove them
+    Delete,
+    /// Move them back onto the boundary and drop their velocity across it
+    Clamp,
+}
+
+/// Static region that deletes every element entering it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbsorberShape<K: Field> {
+    /// Half-open rectangle from `min` to `max`
+    Rect { min: [K; 2], max: [K; 2] },
+    Circle { center: [K; 2], radius: K },
+}
+
+#[derive(Debug, Clone)]
+pub struct Absorber<K: Field> {
+    pub id: u32,
+    pub shape: AbsorberShape<K>,
+    pub absorbed_count: usize,
+    pub absorbed_mass: K,
+}
+
+/// Settings of the neighbor pressure pass, which lets elements behave like a soft fluid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sph<K: Field> {
+    /// Elements further apart than this do not interact
+    pub smoothing_radius: K,
+    /// Pressure per unit of density above `rest_density`
+    pub stiffness: K,
+    /// Density at which the pressure vanishes, below it the pressure pulls neighbors together
+    pub rest_density: K,
+    /// Damping of the relative velocity of neighbors
+    pub viscosity: K,
+}
+
+/// What changed during the last tick, including splits and removals by the population cap
+/// made since the tick before it.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TickReport {
+    /// Elements deleted by cull rules, absorbers, callbacks or the population cap
+    pub particles_removed: u32,
+    /// Elements created by splitting
+    pub particles_added: u32,
+    /// Elements merged into another element
+    pub merges: u32,
+    /// Elements removed by the NaN guard, also counted in `particles_removed`
+    pub non_finite_removed: u32,
+    /// Elements whose pull was summed, fewer than all of them without self-gravity
+    pub force_sources: u32,
+    /// Ticks computed so far, each tick being one time unit
+    pub simulation_time: f64,
+}
+
+/// Differences between two spaces, from `PhysicsSpace::compare`. Elements are matched by id.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Divergence {
+    /// Element count of the first space minus that of the second
+    pub count_difference: i32,
+    /// Elements whose id exists in only one of the spaces
+    pub unmatched: u32,
+    /// Root mean square distance between matched elements
+    pub rms_position: f64,
+    pub max_position: f64,
+    /// Root mean square velocity difference of matched elements
+    pub rms_velocity: f64,
+    pub max_velocity: f64,
+    /// Total mass of the first space minus that of the second
+    pub mass_difference: f64,
+}
+
+#[wasm_bindgen]
+impl Divergence {
+    /// True if every element has a match and no position, velocity or the total mass
+    /// differs by more than `tolerance`.
+    pub fn identical(&self, tolerance: f64) -> bool {
+        self.unmatched == 0
+            && self.max_position <= tolerance
+            && self.max_velocity <= tolerance
+            && self.mass_difference.abs() <= tolerance
+    }
+}
+
+/// Which elements to remove when there are more than the allowed maximum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CapPolicy {
+    Oldest,
+    Lightest,
+    Random,
+    /// Remove the lightest elements, merging each into its nearest remaining neighbor
+    MergeLightest,
+}
+
+/// How `tick` advances elements that are not part of a regularized pair.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    /// Drifts with the old acceleration, then evaluates the new acceleration against the
+    /// other elements at their old positions
+    Hybrid,
+    /// Symplectic leapfrog: half kick, full drift of every element, half kick with the
+    /// acceleration at the drifted positions
+    KickDriftKick,
+}
+
+/// Shape of the force law within the softening length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SofteningKernel {
+    /// Forces fall off with `d / (d² + softening²)^(3/2)`, never exactly Newtonian
+    Plummer,
+    /// Cubic spline with compact support as in SPH codes, exactly Newtonian beyond the
+    /// softening length
+    CubicSpline,
+}
+
+/// When two elements closer than the merge distance are merged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeCriterion {
+    /// Always
+    Distance,
+    /// Only if their relative speed is below the mutual escape velocity `sqrt(2 G (m1 + m2) / d)`
+    Bound,
+}
+
+/// Per element score used to pick the elements worth drawing first.
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ObjectStatus {
+pub enum Importance {
+    /// Speed times mass
+    Momentum,
+    Speed,
+    Mass,
+    /// Magnitude of the current acceleration
+    Acceleration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ObjectStatus {
     Default,
     Deleted,
     MergedInto(usize),
@@ -37,6 +226,7 @@ extern "C" {
     #[wasm_bindgen(js_namespace = console, js_name = log)]
     fn log_many(a: &str, b: &str);
 }
+#[allow(unused_macros)]
 macro_rules! console_log {
     // Note that this is using the `log` function imported above during
     // `bare_bones`
@@ -44,6 +234,7 @@ macro_rules! console_log {
 }
 
 
+#[allow(dead_code)]
 impl<K: Field> PhysicsObject<K> {
     fn clone_change_position(&self, pos_vec: [K; 2]) -> PhysicsObject<K> {
         PhysicsObject {
@@ -51,6 +242,10 @@ impl<K: Field> PhysicsObject<K> {
             direction_vector: self.direction_vector.clone(),
             mass: self.mass.clone(),
             acceleration_vector: self.acceleration_vector.clone(),
+            id: self.id,
+            birth_tick: self.birth_tick,
+            origin: self.origin,
+            protected: self.protected,
             status: self.status,
         }
     }
@@ -61,6 +256,10 @@ impl<K: Field> PhysicsObject<K> {
             position_vector: self.position_vector.clone(),
             mass: self.mass.clone(),
             acceleration_vector: self.acceleration_vector.clone(),
+            id: self.id,
+            birth_tick: self.birth_tick,
+            origin: self.origin,
+            protected: self.protected,
             status: self.status,
         }
     }
@@ -75,6 +274,10 @@ impl<K: Field> PhysicsObject<K> {
             position_vector: pos_vec,
             mass: self.mass.clone(),
             acceleration_vector: self.acceleration_vector.clone(),
+            id: self.id,
+            birth_tick: self.birth_tick,
+            origin: self.origin,
+            protected: self.protected,
             status: self.status,
         }
     }
@@ -84,49 +287,669 @@ impl<K: Field> PhysicsObject<K> {
             position_vector: self.position_vector.clone(),
             mass: self.mass.clone(),
             acceleration_vector: self.acceleration_vector.clone(),
-            status: status,
+            id: self.id,
+            birth_tick: self.birth_tick,
+            origin: self.origin,
+            protected: self.protected,
+            status,
         }
     }
 
     pub fn new(position_vector: [K; 2], direction_vector: [K; 2], mass: K) -> Self {
         PhysicsObject {
-            position_vector: position_vector,
-            direction_vector: direction_vector,
-            mass: mass,
+            position_vector,
+            direction_vector,
+            mass,
             acceleration_vector: [K::zero(), K::zero()],
+            id: 0,
+            birth_tick: 0,
+            origin: ORIGIN_INITIAL,
+            protected: false,
             status: ObjectStatus::Default,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct PhysicsSpace<K: Field + PartialOrd, S: MathSpace<K>> {
     pub elements: Vec<PhysicsObject<K>>,
     gravitational_constant: K,
     math_space: S,
-    radius: K,  //Elements that are further than K away from [0,0] get deleted
+    cull_rules: Vec<CullRule<K>>, //Elements matching any of these get deleted
+    cull_center: CullCenter<K>,
+    plugins: Vec<(u32, Rc<dyn ForcePlugin<K>>)>, //Ids and plugins, in the order of adding
+    external_forces: Vec<Rc<dyn ExternalForce<K>>>,
+    boundary_mode: BoundaryMode,
+    boundary_shape: BoundaryShape<K>,
     epsilon: K, //Small number to fix some numerical errors
+    softening: K, //Softening length of the force law
+    softening_kernel: SofteningKernel,
+    inspiral_rate: K, //Drag on the relative velocity of close pairs
+    inspiral_separation: K, //Pairs closer than this feel the inspiral drag
+    gravity_enabled: bool,
+    self_gravity: bool, //Whether elements up to heavy_threshold attract others
+    heavy_threshold: K, //Elements heavier than this always attract others
+    regularization: Option<(usize, K)>, //Substeps and distance for close pairs
+    regularized_pairs: usize,
+    max_acceleration: [K; 2], //Largest acceleration computed in the last tick
+    escapes: Vec<([K; 2], K)>,
+    absorbers: Vec<Absorber<K>>,
+    accretors: Vec<(u32, K)>, //Ids and capture radii of elements that sweep up crossing elements
+    sph: Option<Sph<K>>,
+    next_absorber_id: u32,
+    next_plugin_id: u32,
+    next_id: u32,
+    report: TickReport,
+    pending_report: TickReport, //Changes made outside of a tick, counted in the next report
+    tick_count: u64,
+    integrator: Integrator,
+    merge_criterion: MergeCriterion,
+    nan_guard: bool,
+    nan_report: Option<u32>, //Id of the last element removed for non-finite values
+    #[allow(dead_code)]
     merge_counter: f64,
 }
 
 impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
     pub fn new(
-        elements: Vec<PhysicsObject<K>>,
+        mut elements: Vec<PhysicsObject<K>>,
         gravitational_constant: K,
         math_space: S,
         radius: K,
         epsilon: K,
     ) -> Self {
+        elements
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, e)| e.id = i as u32);
         Self {
-            elements: elements,
-            gravitational_constant: gravitational_constant,
-            math_space: math_space,
-            radius: radius,
-            epsilon: epsilon,
+            next_id: elements.len() as u32,
+            elements,
+            gravitational_constant,
+            math_space,
+            cull_rules: vec![CullRule::Radius(radius)],
+            cull_center: CullCenter::Fixed([K::zero(), K::zero()]),
+            plugins: Vec::new(),
+            external_forces: Vec::new(),
+            boundary_mode: BoundaryMode::Delete,
+            boundary_shape: BoundaryShape::Circle,
+            epsilon,
+            softening: K::zero(),
+            softening_kernel: SofteningKernel::Plummer,
+            inspiral_rate: K::zero(),
+            inspiral_separation: K::zero(),
+            gravity_enabled: true,
+            self_gravity: true,
+            heavy_threshold: K::zero(),
+            regularization: None,
+            regularized_pairs: 0,
+            max_acceleration: [K::zero(), K::zero()],
+            escapes: Vec::new(),
+            report: TickReport::default(),
+            pending_report: TickReport::default(),
+            absorbers: Vec::new(),
+            accretors: Vec::new(),
+            sph: None,
+            next_absorber_id: 0,
+            next_plugin_id: 0,
+            tick_count: 0,
+            integrator: Integrator::Hybrid,
+            merge_criterion: MergeCriterion::Distance,
+            nan_guard: cfg!(debug_assertions),
+            nan_report: None,
             merge_counter: 0f64,
         }
     }
 
-    fn leapfrog_integration(&self, obj: &PhysicsObject<K>) -> PhysicsObject<K> {
+    /// Number of ticks computed so far.
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    pub fn report(&self) -> &TickReport {
+        &self.report
+    }
+
+    /// Adds the changes made since the last tick, by splitting or the population cap, to the
+    /// report of that tick. Otherwise they are counted in the report of the next tick.
+    pub fn close_report(&mut self) {
+        let pending = std::mem::take(&mut self.pending_report);
+        self.report.particles_removed += pending.particles_removed;
+        self.report.particles_added += pending.particles_added;
+        self.report.merges += pending.merges;
+    }
+
+    /// Offset from the center of mass and mass of every element removed by a cull rule
+    /// during the last tick. The center of mass is taken before any removal.
+    pub fn escapes(&self) -> &[([K; 2], K)] {
+        &self.escapes
+    }
+
+    /// Adds an element, giving it a new id and recording the current tick as its birth tick.
+    pub fn spawn(&mut self, mut element: PhysicsObject<K>) -> usize {
+        element.id = self.new_id();
+        element.birth_tick = self.tick_count;
+        self.elements.push(element);
+        self.elements.len() - 1
+    }
+
+    fn new_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    /// Current index of the element with the given id.
+    pub fn index_of_id(&self, id: u32) -> Option<usize> {
+        self.elements.iter().position(|e| e.id == id)
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    pub fn set_merge_criterion(&mut self, criterion: MergeCriterion) {
+        self.merge_criterion = criterion;
+    }
+
+    /// Whether `s` would be merged into `f`.
+    fn should_merge(&self, f: &PhysicsObject<K>, s: &PhysicsObject<K>) -> bool {
+        let m = &self.math_space;
+        let distance = m.distance(&f.position_vector, &s.position_vector);
+        distance < self.epsilon
+            && match self.merge_criterion {
+                MergeCriterion::Distance => true,
+                MergeCriterion::Bound => {
+                    // v² < 2 G (m1 + m2) / d, multiplied by d to allow d = 0
+                    let origin = [K::zero(), K::zero()];
+                    let relative = m.sub(&f.direction_vector, &s.direction_vector);
+                    let speed = m.distance(&origin, &relative);
+                    let two = K::one() + K::one();
+                    speed.clone() * speed * distance.clone()
+                        < two
+                            * self.gravitational_constant.clone()
+                            * (f.mass.clone() + s.mass.clone())
+                }
+            }
+    }
+
+    /// Removes elements with a non-finite position or velocity during each tick, before they
+    /// can turn every other element's acceleration into NaN. On by default in debug builds.
+    pub fn set_nan_guard(&mut self, enabled: bool) {
+        self.nan_guard = enabled;
+    }
+
+    /// Id of the first element removed by the NaN guard during the last tick that removed any.
+    pub fn last_nan_report(&self) -> Option<u32> {
+        self.nan_report
+    }
+
+    /// Removes deleted and merged elements, counting them in the report. This is the only
+    /// place elements marked during a tick leave the list; new removal reasons only need to
+    /// mark elements as deleted.
+    fn compact(&mut self, elements: &mut Vec<PhysicsObject<K>>) {
+        for e in elements.iter() {
+            match e.status {
+                ObjectStatus::Deleted => self.report.particles_removed += 1,
+                ObjectStatus::MergedInto(_) => self.report.merges += 1,
+                ObjectStatus::Default => {}
+            }
+        }
+        elements.retain(|e| e.status == ObjectStatus::Default);
+    }
+
+    /// Marks an element for removal in the removal pass of the next tick. It neither merges
+    /// nor exerts any force in that tick. Returns false if the index is out of range.
+    pub fn delete(&mut self, index: usize) -> bool {
+        match self.elements.get_mut(index) {
+            Some(e) => {
+                e.status = ObjectStatus::Deleted;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks elements with a non-finite position or velocity as deleted, returning the id of
+    /// the first one.
+    fn quarantine_non_finite(&mut self, elements: &mut [PhysicsObject<K>]) -> Option<u32> {
+        if !self.nan_guard {
+            return None;
+        }
+        let mut first = None;
+        for e in elements.iter_mut().filter(|e| e.status == ObjectStatus::Default) {
+            if !e.position_vector.iter().chain(&e.direction_vector).all(K::is_finite) {
+                e.status = ObjectStatus::Deleted;
+                first = first.or(Some(e.id));
+                self.report.non_finite_removed += 1;
+            }
+        }
+        first
+    }
+
+    pub fn add_cull_rule(&mut self, rule: CullRule<K>) {
+        self.cull_rules.push(rule);
+    }
+
+    /// Replaces any `CullRule::MaxAge` by one with the given lifetime, or only removes them
+    /// for `None`.
+    pub fn set_max_age(&mut self, ticks: Option<u64>) {
+        self.cull_rules
+            .retain(|rule| !matches!(rule, CullRule::MaxAge(_)));
+        if let Some(ticks) = ticks {
+            self.cull_rules.push(CullRule::MaxAge(ticks));
+        }
+    }
+
+    pub fn clear_cull_rules(&mut self) {
+        self.cull_rules.clear();
+    }
+
+    /// The smallest radius of the radius rules, `None` if there is none.
+    pub fn cull_radius(&self) -> Option<K> {
+        self.cull_rules.iter().fold(None, |min, rule| match (min, rule) {
+            (Some(m), CullRule::Radius(r)) if m <= *r => Some(m),
+            (_, CullRule::Radius(r)) => Some(r.clone()),
+            (min, _) => min,
+        })
+    }
+
+    /// Sets the point radius rules are measured from. Defaults to [0,0].
+    pub fn set_cull_center(&mut self, center: CullCenter<K>) {
+        self.cull_center = center;
+    }
+
+    /// Point radius rules are measured from: the fixed cull center or the center of mass.
+    pub fn cull_origin(&self) -> [K; 2] {
+        match &self.cull_center {
+            CullCenter::Fixed(point) => point.clone(),
+            CullCenter::CenterOfMass => self.center_of_mass(),
+        }
+    }
+
+    /// Adds a force evaluated for every element after gravity, in the order of adding.
+    /// Returns its id.
+    pub fn add_plugin(&mut self, plugin: Box<dyn ForcePlugin<K>>) -> u32 {
+        let id = self.next_plugin_id;
+        self.next_plugin_id += 1;
+        self.plugins.push((id, Rc::from(plugin)));
+        id
+    }
+
+    /// Swaps plugin `id` for `plugin`, keeping its place in the order. Returns false for
+    /// unknown ids.
+    pub fn replace_plugin(&mut self, id: u32, plugin: Box<dyn ForcePlugin<K>>) -> bool {
+        match self.plugins.iter_mut().find(|(other, _)| *other == id) {
+            Some(entry) => {
+                entry.1 = Rc::from(plugin);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_plugin(&mut self, id: u32) -> bool {
+        let len = self.plugins.len();
+        self.plugins.retain(|(other, _)| *other != id);
+        self.plugins.len() != len
+    }
+
+    pub fn clear_plugins(&mut self) {
+        self.plugins.clear();
+    }
+
+    /// Adds a force that may change with the simulation time, like a moving attractor.
+    pub fn add_external_force(&mut self, force: Box<dyn ExternalForce<K>>) {
+        self.external_forces.push(Rc::from(force));
+    }
+
+    pub fn clear_external_forces(&mut self) {
+        self.external_forces.clear();
+    }
+
+    pub fn set_boundary_mode(&mut self, mode: BoundaryMode) {
+        self.boundary_mode = mode;
+    }
+
+    pub fn set_boundary_shape(&mut self, shape: BoundaryShape<K>) {
+        self.boundary_shape = shape;
+    }
+
+    /// Moves an element beyond a radius rule back onto the boundary circle and removes the
+    /// radial part of its velocity, keeping the tangential part. For a rectangle, the
+    /// velocity along the axis of every crossed side is removed.
+    fn clamp_to_boundary(&self, e: &mut PhysicsObject<K>, center: &[K; 2]) {
+        if let BoundaryShape::Rect { min, max } = &self.boundary_shape {
+            for axis in 0..2 {
+                let p = &e.position_vector[axis];
+                let wall = if *p < min[axis] {
+                    &min[axis]
+                } else if *p > max[axis] {
+                    &max[axis]
+                } else {
+                    continue;
+                };
+                e.position_vector[axis] = wall.clone();
+                e.direction_vector[axis] = K::zero();
+            }
+            return;
+        }
+        let m = &self.math_space;
+        for rule in self.cull_rules.iter() {
+            if let CullRule::Radius(r) = rule {
+                let offset = m.sub(&e.position_vector, center);
+                let distance = m.distance(center, &e.position_vector);
+                if distance > *r {
+                    let normal = m.mul(&distance.inv(), &offset);
+                    let v = &e.direction_vector;
+                    let radial =
+                        normal[0].clone() * v[0].clone() + normal[1].clone() * v[1].clone();
+                    e.position_vector = m.add(center, &m.mul(r, &normal));
+                    e.direction_vector = m.sub(&e.direction_vector, &m.mul(&radial, &normal));
+                }
+            }
+        }
+    }
+
+    fn is_culled(&self, e: &PhysicsObject<K>, center: &[K; 2]) -> bool {
+        let m = &self.math_space;
+        let origin = [K::zero(), K::zero()];
+        let clamped = self.boundary_mode == BoundaryMode::Clamp;
+        let outside = match &self.boundary_shape {
+            BoundaryShape::Circle => false,
+            BoundaryShape::Rect { min, max } => (0..2).any(|axis| {
+                let p = &e.position_vector[axis];
+                *p < min[axis] || *p > max[axis]
+            }),
+        };
+        let rect = self.boundary_shape != BoundaryShape::Circle;
+        !e.protected && ((outside && !clamped) || self.cull_rules.iter().any(|rule| match rule {
+            CullRule::Radius(_) if clamped || rect => false,
+            CullRule::Radius(r) => {
+                *r < K::zero()
+                    || m.distance_squared(center, &e.position_vector) > r.clone() * r.clone()
+            }
+            CullRule::MinSpeed(v) => m.distance(&origin, &e.direction_vector) < *v,
+            CullRule::MaxSpeed(v) => m.distance(&origin, &e.direction_vector) > *v,
+            CullRule::MinMass(mass) => e.mass < *mass,
+            CullRule::MaxAge(ticks) => self.tick_count.saturating_sub(e.birth_tick) > *ticks,
+        }))
+    }
+
+    /// Slowly removes orbital energy from pairs closer than `separation` by damping their
+    /// relative velocity with `rate`, so close binaries spiral together. Momentum is kept.
+    /// This is a toy approximation of gravitational-wave inspiral, not general relativity.
+    /// The drag acts on every close pair, also while gravity or self-gravity is off.
+    pub fn set_binary_inspiral(&mut self, rate: K, separation: K) {
+        self.inspiral_rate = rate;
+        self.inspiral_separation = separation;
+    }
+
+    /// Turns the force computation on or off. While off, elements move in straight lines.
+    /// Accelerations are recomputed when gravity is switched back on, so the next step
+    /// does not integrate with stale values.
+    pub fn set_gravity_enabled(&mut self, enabled: bool) {
+        if enabled != self.gravity_enabled {
+            self.gravity_enabled = enabled;
+            self.compute_accelerations();
+        }
+    }
+
+    /// Stores the acceleration of every element at its current position and time without
+    /// moving anything, so the field of a static configuration can be inspected.
+    pub fn compute_accelerations(&mut self) {
+        let time = self.tick_count as f64;
+        let accelerations: Vec<[K; 2]> = self
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(i, e)| self.acceleration(i, e, &e.position_vector, time))
+            .collect();
+        self.elements
+            .iter_mut()
+            .zip(accelerations)
+            .for_each(|(e, a)| e.acceleration_vector = a);
+    }
+
+    /// Enables or disables the neighbor pressure pass. It coexists with gravity, so
+    /// self-gravitating gas blobs are possible.
+    pub fn set_sph(&mut self, sph: Option<Sph<K>>) {
+        self.sph = sph;
+    }
+
+    /// Kernel-weighted mass `Σ m_j (h² - r²)³ / h⁸` around every element, with `h` the
+    /// smoothing radius. This is the poly6 density without its constant `4/π` factor.
+    /// Empty while the pressure pass is disabled.
+    pub fn densities(&self) -> Vec<K> {
+        let sph = match &self.sph {
+            Some(sph) => sph,
+            None => return Vec::new(),
+        };
+        let m = &self.math_space;
+        let h = &sph.smoothing_radius;
+        let h2 = h.clone() * h.clone();
+        let norm = (h2.clone() * h2.clone() * h2.clone() * h2.clone()).inv();
+        self.elements
+            .iter()
+            .map(|e1| {
+                self.elements.iter().fold(K::zero(), |density, e2| {
+                    let r = m.distance(&e1.position_vector, &e2.position_vector);
+                    if r < *h {
+                        let d = h2.clone() - r.clone() * r;
+                        density + e2.mass.clone() * d.clone() * d.clone() * d * norm.clone()
+                    } else {
+                        density
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Pressure and viscosity accelerations using the spiky kernel gradient and the viscosity
+    /// kernel laplacian, with pressure `stiffness * (density - rest_density)`. Neighbors are
+    /// found by checking all pairs.
+    fn sph_accelerations(&self, sph: &Sph<K>) -> Vec<[K; 2]> {
+        let m = &self.math_space;
+        let h = &sph.smoothing_radius;
+        let h2 = h.clone() * h.clone();
+        let norm = (h2.clone() * h2 * h.clone()).inv();
+        let half = (K::one() + K::one()).inv();
+        let densities = self.densities();
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, e1)| {
+                let force = self.elements.iter().enumerate().fold(
+                    [K::zero(), K::zero()],
+                    |force, (j, e2)| {
+                        let r = m.distance(&e1.position_vector, &e2.position_vector);
+                        if i == j || r.is_zero() || r >= *h {
+                            return force;
+                        }
+                        let falloff = h.clone() - r.clone();
+                        let excess = (densities[i].clone() + densities[j].clone()) * half.clone()
+                            - sph.rest_density.clone();
+                        // Negative pressure holds the fluid together. The near term keeps close
+                        // pairs apart, so they cannot collapse onto each other.
+                        let q = falloff.clone() * h.clone().inv();
+                        let near = sph.rest_density.clone() * q.clone() * q.clone() * q;
+                        let pressure = sph.stiffness.clone() * (excess + near);
+                        let push = e2.mass.clone()
+                            * pressure
+                            * densities[j].clone().inv()
+                            * falloff.clone()
+                            * falloff.clone()
+                            * norm.clone()
+                            * r.clone().inv();
+                        let drag = sph.viscosity.clone()
+                            * e2.mass.clone()
+                            * densities[j].clone().inv()
+                            * falloff
+                            * norm.clone();
+                        let away = m.sub(&e1.position_vector, &e2.position_vector);
+                        let relative = m.sub(&e2.direction_vector, &e1.direction_vector);
+                        m.add(&m.add(&force, &m.mul(&push, &away)), &m.mul(&drag, &relative))
+                    },
+                );
+                m.mul(&densities[i].clone().inv(), &force)
+            })
+            .collect()
+    }
+
+    pub fn add_absorber(&mut self, shape: AbsorberShape<K>) -> u32 {
+        let id = self.next_absorber_id;
+        self.next_absorber_id += 1;
+        self.absorbers.push(Absorber {
+            id,
+            shape,
+            absorbed_count: 0,
+            absorbed_mass: K::zero(),
+        });
+        id
+    }
+
+    pub fn remove_absorber(&mut self, id: u32) -> bool {
+        let len = self.absorbers.len();
+        self.absorbers.retain(|a| a.id != id);
+        self.absorbers.len() != len
+    }
+
+    pub fn absorber(&self, id: u32) -> Option<&Absorber<K>> {
+        self.absorbers.iter().find(|a| a.id == id)
+    }
+
+    /// Whether `e` lies inside an absorber, crediting its mass to that absorber if so.
+    /// Absorbers are tested one by one against every element.
+    fn absorb(&mut self, e: &PhysicsObject<K>) -> bool {
+        let m = &self.math_space;
+        let p = &e.position_vector;
+        let hit = self.absorbers.iter_mut().find(|a| match &a.shape {
+            AbsorberShape::Rect { min, max } => {
+                p[0] >= min[0] && p[0] < max[0] && p[1] >= min[1] && p[1] < max[1]
+            }
+            AbsorberShape::Circle { center, radius } => m.distance(center, p) < *radius,
+        });
+        match hit {
+            Some(absorber) => {
+                absorber.absorbed_count += 1;
+                absorber.absorbed_mass = absorber.absorbed_mass.clone() + e.mass.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes element `id` capture every element whose path during a tick passes within
+    /// `radius` of it, even if both ends of the path are further away. `None` removes it.
+    pub fn set_accretor(&mut self, id: u32, radius: Option<K>) {
+        self.accretors.retain(|(other, _)| *other != id);
+        if let Some(radius) = radius {
+            self.accretors.push((id, radius));
+        }
+    }
+
+    /// Merges the elements whose straight path from `start` to their position in `next`
+    /// came within the capture radius of an accretor into it. Paths are taken relative to
+    /// the accretor, so each element is tested once per accretor.
+    fn sweep_accretors(&self, start: &[[K; 2]], next: &mut [PhysicsObject<K>]) {
+        let m = &self.math_space;
+        let is_accretor = |id: u32| self.accretors.iter().any(|(other, _)| *other == id);
+        for (id, radius) in self.accretors.iter() {
+            let a = match next.iter().position(|e| e.id == *id) {
+                Some(a) if next[a].status == ObjectStatus::Default => a,
+                _ => continue,
+            };
+            let (from, to) = (start[a].clone(), next[a].position_vector.clone());
+            for i in 0..next.len() {
+                if i == a || next[i].status != ObjectStatus::Default || is_accretor(next[i].id) {
+                    continue;
+                }
+                let begin = m.sub(&start[i], &from);
+                let end = m.sub(&next[i].position_vector, &to);
+                if closest_approach_squared(&begin, &end) < radius.clone() * radius.clone() {
+                    next[a] = self.merge(&next[a], &next[i]);
+                    next[i].status = ObjectStatus::MergedInto(a);
+                }
+            }
+        }
+    }
+
+    /// Integrates pairs closer than `distance` as an isolated two-body problem with
+    /// `substeps` substeps per tick, while the pull of the rest of the system on them is
+    /// held constant over the tick. `None` disables the regularization.
+    pub fn set_binary_regularization(&mut self, regularization: Option<(usize, K)>) {
+        self.regularization = regularization;
+    }
+
+    /// Disabling self-gravity turns the simulation into a restricted problem: only elements
+    /// heavier than the heavy threshold attract, every other element moves as a test mass
+    /// in their field. The force sum then only runs over the heavy elements.
+    pub fn set_self_gravity(&mut self, enabled: bool) {
+        self.self_gravity = enabled;
+    }
+
+    pub fn self_gravity(&self) -> bool {
+        self.self_gravity
+    }
+
+    /// Elements heavier than this attract the others even without self-gravity.
+    pub fn set_heavy_threshold(&mut self, mass: K) {
+        self.heavy_threshold = mass;
+    }
+
+    pub fn heavy_threshold(&self) -> K {
+        self.heavy_threshold.clone()
+    }
+
+    /// Whether `e` attracts the other elements.
+    fn attracts(&self, e: &PhysicsObject<K>) -> bool {
+        self.self_gravity || e.mass > self.heavy_threshold
+    }
+
+    /// Number of pairs that were regularized in the last tick.
+    pub fn regularized_pairs(&self) -> usize {
+        self.regularized_pairs
+    }
+
+    /// Magnitude of the largest acceleration computed during the last tick, including
+    /// elements removed at its end.
+    pub fn max_acceleration(&self) -> K {
+        let origin = [K::zero(), K::zero()];
+        self.math_space.distance(&origin, &self.max_acceleration)
+    }
+
+    /// Softening length: forces within it are weaker than `1 / d²`, which bounds them for
+    /// overlapping elements. Zero by default.
+    pub fn set_softening(&mut self, softening: K) {
+        self.softening = softening;
+    }
+
+    pub fn softening(&self) -> K {
+        self.softening.clone()
+    }
+
+    /// Selects how forces are softened, `SofteningKernel::Plummer` by default.
+    pub fn set_softening_kernel(&mut self, kernel: SofteningKernel) {
+        self.softening_kernel = kernel;
+    }
+
+    pub fn softening_kernel(&self) -> SofteningKernel {
+        self.softening_kernel
+    }
+
+    /// Distance below which two elements are merged.
+    pub fn epsilon(&self) -> K {
+        self.epsilon.clone()
+    }
+
+    pub fn set_gravitational_constant(&mut self, gravitational_constant: K) {
+        self.gravitational_constant = gravitational_constant;
+    }
+
+    pub fn gravitational_constant(&self) -> K {
+        self.gravitational_constant.clone()
+    }
+
+    fn leapfrog_integration(&self, index: usize, obj: &PhysicsObject<K>) -> PhysicsObject<K> {
        // console_log!("Particle {:?}", obj);
         
         let m = &self.math_space;
@@ -140,8 +963,10 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
         );
         //a(i+1)
         let next_acc = self.acceleration(
+            index,
             &obj.clone_change_position(next_pos.clone()),
             &obj.position_vector,
+            self.tick_count as f64 + 1.0,
         );
 
         //v(i+1) = v(i) + 0.5( a(i+1) + a(i) )
@@ -155,44 +980,117 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
             direction_vector: next_dir,
             acceleration_vector: next_acc,
             mass: obj.mass.clone(),
+            id: obj.id,
+            birth_tick: obj.birth_tick,
+            origin: obj.origin,
+            protected: obj.protected,
             status: obj.status,
         }
     }
 
-    fn euler_integration(&self, obj: &PhysicsObject<K>) -> PhysicsObject<K> {
+    /// Opening half kick and full drift of a kick-drift-kick step. The closing half kick
+    /// needs the drifted positions of all elements and is applied in `tick`.
+    fn kick_drift(&self, obj: &PhysicsObject<K>) -> PhysicsObject<K> {
+        let m = &self.math_space;
+        let half = (K::one() + K::one()).inv();
+        let mut next = obj.clone();
+        next.direction_vector =
+            m.add(&obj.direction_vector, &m.mul(&half, &obj.acceleration_vector));
+        next.position_vector = m.add(&obj.position_vector, &next.direction_vector);
+        next
+    }
+
+    #[allow(dead_code)]
+    fn euler_integration(&self, index: usize, obj: &PhysicsObject<K>) -> PhysicsObject<K> {
         let m = &self.math_space;
         let next_obj =
             obj.clone_change_position(m.add(&obj.position_vector, &obj.direction_vector));
+        let time = self.tick_count as f64 + 1.0;
         println!(
             "Acceleration {:?}",
-            &self.acceleration(&next_obj, &obj.position_vector)
+            &self.acceleration(index, &next_obj, &obj.position_vector, time)
         );
         next_obj.clone_change_direction(m.add(
             &next_obj.direction_vector,
-            &self.acceleration(&next_obj, &obj.position_vector),
+            &self.acceleration(index, &next_obj, &obj.position_vector, time),
         ))
     }
 
-    fn acceleration(&self, e1: &PhysicsObject<K>, old_pos: &[K; 2]) -> [K; 2] {
+    /// Acceleration on `e1`, the element at `index`, from gravity, the force plugins and the
+    /// external forces, the latter evaluated at simulation time `time`, the time `e1` is at.
+    fn acceleration(
+        &self,
+        index: usize,
+        e1: &PhysicsObject<K>,
+        old_pos: &[K; 2],
+        time: f64,
+    ) -> [K; 2] {
+        let mut total = self.gravity(e1, old_pos);
+        if !self.inspiral_rate.is_zero() {
+            total = self.math_space.add(&total, &self.inspiral(index, e1));
+        }
+        for force in self.external_forces.iter() {
+            total = self.math_space.add(&total, &force.acceleration(&e1.position_vector, time));
+        }
+        if !self.plugins.is_empty() {
+            let neighbors = DirectNeighbors {
+                elements: &self.elements,
+                math_space: &self.math_space,
+            };
+            for (_, plugin) in self.plugins.iter() {
+                plugin.accumulate(index, e1, &neighbors, &mut total);
+            }
+        }
+        total
+    }
+
+    /// Drag of the binary inspiral on `e1`, from every other element closer than the
+    /// inspiral separation. It does not depend on gravity being enabled or on which
+    /// elements attract others.
+    fn inspiral(&self, index: usize, e1: &PhysicsObject<K>) -> [K; 2] {
         let m = &self.math_space;
         self.elements
             .iter()
+            .enumerate()
+            .filter(|(j, e2)| {
+                *j != index
+                    && m.distance(&e2.position_vector, &e1.position_vector)
+                        < self.inspiral_separation
+            })
+            .fold([K::zero(), K::zero()], |total, (_, e2)| {
+                let total_mass = e1.mass.clone() + e2.mass.clone();
+                if total_mass.is_zero() {
+                    return total;
+                }
+                // Damp the relative velocity, weighted by the reduced mass
+                let weight = self.inspiral_rate.clone() * e2.mass.clone() * total_mass.inv();
+                let relative = m.sub(&e1.direction_vector, &e2.direction_vector);
+                m.sub(&total, &m.mul(&weight, &relative))
+            })
+    }
+
+    fn gravity(&self, e1: &PhysicsObject<K>, old_pos: &[K; 2]) -> [K; 2] {
+        if !self.gravity_enabled {
+            return [K::zero(), K::zero()];
+        }
+        let m = &self.math_space;
+        self.elements
+            .iter()
+            .filter(|e2| self.attracts(e2))
             .map(|e2| {
                 //Calculate the gravity effect on e1 while being attracted by e2
                 let distance = m.distance(&e2.position_vector, &e1.position_vector);
-                let old_distance = m.distance(&e2.position_vector, &old_pos);
+                let old_distance = m.distance(&e2.position_vector, old_pos);
                 //           println!("Distance {:?}",distance);
                 if !(distance.is_zero() || old_distance.is_zero()) {
                     let distance_vector = m.sub(&e2.position_vector, &e1.position_vector);
                     //             println!("Distance vector {:?}", distance_vector);
 
-                    let distance_unit_vector = m.mul(&distance.clone().inv(), &distance_vector);
-                    //           println!("Distance unit vector {:?}", distance_unit_vector);
                     let acceleration = e2.mass.clone()
                         * self.gravitational_constant.clone()
-                        * ((distance.clone() * distance.clone()).inv());
+                        * self.inverse_cube(distance.clone());
                     //         println!("Acceleration {:?}", acceleration);
-                    m.mul(&acceleration, &distance_unit_vector)
+                    m.mul(&acceleration, &distance_vector)
                 } else {
                     [K::zero(), K::zero()]
                 }
@@ -200,6 +1098,232 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
             .fold([K::zero(), K::zero()], |a, acc| m.add(&a, &acc))
     }
 
+    /// `sqrt(distance² + softening²)`, the distance used in place of `distance` by the
+    /// force law.
+    fn softened(&self, distance: K) -> K {
+        if self.softening.is_zero() {
+            return distance;
+        }
+        let origin = [K::zero(), K::zero()];
+        self.math_space.distance(&origin, &[distance, self.softening.clone()])
+    }
+
+    /// Softened `1 / distance³`: the acceleration towards a unit mass at `distance`, with
+    /// G = 1, is this times the offset to it.
+    fn inverse_cube(&self, distance: K) -> K {
+        if self.softening.is_zero() {
+            return (distance.clone() * distance.clone() * distance).inv();
+        }
+        match self.softening_kernel {
+            SofteningKernel::Plummer => {
+                let softened = self.softened(distance);
+                (softened.clone() * softened.clone() * softened).inv()
+            }
+            SofteningKernel::CubicSpline => {
+                let h = self.softening.clone();
+                if distance >= h {
+                    return (distance.clone() * distance.clone() * distance).inv();
+                }
+                let u = distance * h.clone().inv();
+                let u2 = u.clone() * u.clone();
+                let h3 = (h.clone() * h.clone() * h).inv();
+                h3 * if u < ratio::<K>(1, 2) {
+                    ratio::<K>(32, 3) + u2 * (ratio::<K>(32, 1) * u - ratio::<K>(192, 5))
+                } else {
+                    let u3 = u2.clone() * u.clone();
+                    ratio::<K>(64, 3) - ratio::<K>(48, 1) * u + ratio::<K>(192, 5) * u2
+                        - ratio::<K>(32, 3) * u3.clone()
+                        - (ratio::<K>(15, 1) * u3).inv()
+                }
+            }
+        }
+    }
+
+    /// Softened `1 / distance`, the potential of a unit mass at `distance` with G = 1 and
+    /// the sign flipped.
+    fn inverse_distance(&self, distance: K) -> K {
+        let h = self.softening.clone();
+        if self.softening_kernel == SofteningKernel::Plummer || h.is_zero() {
+            return self.softened(distance).inv();
+        }
+        if distance >= h {
+            return distance.inv();
+        }
+        let u = distance * h.clone().inv();
+        let u2 = u.clone() * u.clone();
+        h.inv()
+            * if u < ratio::<K>(1, 2) {
+                ratio::<K>(14, 5)
+                    - u2.clone()
+                        * (ratio::<K>(16, 3)
+                            + u2 * (ratio::<K>(32, 5) * u - ratio::<K>(48, 5)))
+            } else {
+                ratio::<K>(16, 5)
+                    - (ratio::<K>(15, 1) * u.clone()).inv()
+                    - u2 * (ratio::<K>(32, 3)
+                        + u.clone()
+                            * (u.clone() * (ratio::<K>(48, 5) - ratio::<K>(32, 15) * u)
+                                - ratio::<K>(16, 1)))
+            }
+    }
+
+    /// Acceleration `e2` alone causes on an element at `position`, zero if `e2` does not
+    /// attract, gravity is disabled or `e2` is at `position`, like in `gravity`.
+    fn pull(&self, e2: &PhysicsObject<K>, position: &[K; 2]) -> [K; 2] {
+        if !self.gravity_enabled || !self.attracts(e2) {
+            return [K::zero(), K::zero()];
+        }
+        let m = &self.math_space;
+        let distance = m.distance(&e2.position_vector, position);
+        if distance.is_zero() {
+            return [K::zero(), K::zero()];
+        }
+        let strength = self.gravitational_constant.clone()
+            * e2.mass.clone()
+            * self.inverse_cube(distance);
+        m.mul(&strength, &m.sub(&e2.position_vector, position))
+    }
+
+    /// Greedily pairs up elements closer than `distance`, each element in at most one pair.
+    fn close_pairs(&self, distance: &K) -> Vec<(usize, usize)> {
+        let m = &self.math_space;
+        let mut paired = vec![false; self.elements.len()];
+        let mut pairs = Vec::new();
+        for i in 0..self.elements.len() {
+            if paired[i] {
+                continue;
+            }
+            let position = &self.elements[i].position_vector;
+            let partner = (i + 1..self.elements.len()).find(|&j| {
+                !paired[j] && m.distance(position, &self.elements[j].position_vector) < *distance
+            });
+            if let Some(j) = partner {
+                paired[i] = true;
+                paired[j] = true;
+                pairs.push((i, j));
+            }
+        }
+        pairs
+    }
+
+    /// Advances a close pair by one tick: their barycenter moves under the pull of the rest
+    /// of the system like an unpaired element, their relative orbit is integrated in
+    /// `substeps` kick-drift-kick substeps.
+    fn integrate_pair(
+        &self,
+        i: usize,
+        j: usize,
+        substeps: usize,
+    ) -> (PhysicsObject<K>, PhysicsObject<K>) {
+        let m = &self.math_space;
+        let (e1, e2) = (&self.elements[i], &self.elements[j]);
+        let half = (K::one() + K::one()).inv();
+        let total = e1.mass.clone() + e2.mass.clone();
+        let w1 = e1.mass.clone() * total.clone().inv();
+        let w2 = e2.mass.clone() * total.clone().inv();
+
+        // Pull of everything except the partner, held constant over the tick
+        let external1 = m.sub(
+            &self.acceleration(i, e1, &e1.position_vector, self.tick_count as f64),
+            &self.pull(e2, &e1.position_vector),
+        );
+        let external2 = m.sub(
+            &self.acceleration(j, e2, &e2.position_vector, self.tick_count as f64),
+            &self.pull(e1, &e2.position_vector),
+        );
+        let center_acc = m.add(&m.mul(&w1, &external1), &m.mul(&w2, &external2));
+        let tidal_acc = m.sub(&external2, &external1);
+        let center_pos = m.add(
+            &m.mul(&w1, &e1.position_vector),
+            &m.mul(&w2, &e2.position_vector),
+        );
+        let center_dir = m.add(
+            &m.mul(&w1, &e1.direction_vector),
+            &m.mul(&w2, &e2.direction_vector),
+        );
+
+        // Only partners that attract contribute to the relative pull
+        let attracting = [e1, e2]
+            .iter()
+            .filter(|e| self.attracts(e))
+            .fold(K::zero(), |sum, e| sum + e.mass.clone());
+        let mu = self.gravitational_constant.clone() * attracting;
+        let h = (0..substeps).fold(K::zero(), |n, _| n + K::one()).inv();
+        let half_h = half.clone() * h.clone();
+        // Relative orbit with the tidal pull changing linearly to `tidal_end` over the tick
+        let relative_orbit = |tidal_end: &[K; 2]| {
+            let change = m.sub(tidal_end, &tidal_acc);
+            let relative_acc = |r: &[K; 2], t: &K| {
+                let tidal = m.add(&tidal_acc, &m.mul(t, &change));
+                let distance = m.distance(&[K::zero(), K::zero()], r);
+                if !self.gravity_enabled || distance.is_zero() {
+                    return tidal;
+                }
+                let strength = mu.clone() * self.inverse_cube(distance);
+                m.sub(&tidal, &m.mul(&strength, r))
+            };
+            let mut relative_pos = m.sub(&e2.position_vector, &e1.position_vector);
+            let mut relative_dir = m.sub(&e2.direction_vector, &e1.direction_vector);
+            let mut t = K::zero();
+            for _ in 0..substeps {
+                let kick = relative_acc(&relative_pos, &t);
+                relative_dir = m.add(&relative_dir, &m.mul(&half_h, &kick));
+                relative_pos = m.add(&relative_pos, &m.mul(&h, &relative_dir));
+                t = t + h.clone();
+                let kick = relative_acc(&relative_pos, &t);
+                relative_dir = m.add(&relative_dir, &m.mul(&half_h, &kick));
+            }
+            (relative_pos, relative_dir)
+        };
+
+        let next_center_pos =
+            m.add(&m.add(&center_pos, &center_dir), &m.mul(&half, &center_acc));
+        let place = |relative_pos: &[K; 2]| {
+            (
+                e1.clone_change_position(m.sub(&next_center_pos, &m.mul(&w2, relative_pos))),
+                e2.clone_change_position(m.add(&next_center_pos, &m.mul(&w1, relative_pos))),
+            )
+        };
+        // Pull of the rest at the new positions, evaluated at the end of the tick
+        let time = self.tick_count as f64 + 1.0;
+        let externals = |next1: &PhysicsObject<K>, next2: &PhysicsObject<K>| {
+            (
+                m.sub(
+                    &self.acceleration(i, next1, &e1.position_vector, time),
+                    &self.pull(e2, &next1.position_vector),
+                ),
+                m.sub(
+                    &self.acceleration(j, next2, &e2.position_vector, time),
+                    &self.pull(e1, &next2.position_vector),
+                ),
+            )
+        };
+
+        // Predict the orbit with a constant tidal pull, then integrate it again with the
+        // tidal pull moving towards its value at the predicted positions
+        let (predicted, _) = relative_orbit(&tidal_acc);
+        let (next1, next2) = place(&predicted);
+        let (next_external1, next_external2) = externals(&next1, &next2);
+        let (relative_pos, relative_dir) =
+            relative_orbit(&m.sub(&next_external2, &next_external1));
+        let (mut next1, mut next2) = place(&relative_pos);
+        let (next_external1, next_external2) = externals(&next1, &next2);
+
+        // Close the center's kick with the pull at the new positions, like the leapfrog of
+        // unpaired elements
+        let next_center_acc =
+            m.add(&m.mul(&w1, &next_external1), &m.mul(&w2, &next_external2));
+        let next_center_dir =
+            m.add(&center_dir, &m.mul(&half, &m.add(&center_acc, &next_center_acc)));
+        next1.direction_vector = m.sub(&next_center_dir, &m.mul(&w2, &relative_dir));
+        next2.direction_vector = m.add(&next_center_dir, &m.mul(&w1, &relative_dir));
+        next1.acceleration_vector =
+            m.add(&next_external1, &self.pull(&next2, &next1.position_vector));
+        next2.acceleration_vector =
+            m.add(&next_external2, &self.pull(&next1, &next2.position_vector));
+        (next1, next2)
+    }
+
     fn merge(&self, f: &PhysicsObject<K>, s: &PhysicsObject<K>) -> PhysicsObject<K> {
     //    console_log!("#########################Merging {:?} with {:?}", f, s);
 
@@ -227,6 +1351,10 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
                 ),
             ),
         //    acceleration_vector: [K::zero(), K::zero()],
+            id: f.id,
+            birth_tick: f.birth_tick.min(s.birth_tick),
+            origin: f.origin,
+            protected: f.protected || s.protected,
             status: ObjectStatus::Default,
             mass: f.mass.clone() + s.mass.clone(), //Sum of masses
         };
@@ -234,6 +1362,286 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
         p
     }
 
+    /// Net force `Σ m a` on all elements, using their current accelerations. Internal
+    /// gravitational forces cancel pairwise, so this is close to zero for a closed system.
+    pub fn net_force(&self) -> [K; 2] {
+        let m = &self.math_space;
+        self.elements.iter().fold([K::zero(), K::zero()], |force, e| {
+            m.add(&force, &m.mul(&e.mass, &e.acceleration_vector))
+        })
+    }
+
+    /// Net torque `Σ r × m a` of all elements about `point`, using their current accelerations.
+    /// Internal gravitational torques cancel, so this is close to zero for a closed system.
+    pub fn net_torque_about(&self, point: &[K; 2]) -> K {
+        let m = &self.math_space;
+        self.elements.iter().fold(K::zero(), |torque, e| {
+            let r = m.sub(&e.position_vector, point);
+            let f = m.mul(&e.mass, &e.acceleration_vector);
+            torque + r[0].clone() * f[1].clone() - r[1].clone() * f[0].clone()
+        })
+    }
+
+    /// Gravitational acceleration a massless test particle at `point` would feel from the
+    /// elements that attract, zero while gravity is disabled.
+    pub fn field_at(&self, point: &[K; 2]) -> [K; 2] {
+        let m = &self.math_space;
+        self.elements
+            .iter()
+            .fold([K::zero(), K::zero()], |field, e| m.add(&field, &self.pull(e, point)))
+    }
+
+    /// Positions of a massless test particle after every `stride` of `steps` kick-drift-kick
+    /// steps through the field of the elements, which are held in place. Nothing is changed.
+    pub fn predict(
+        &self,
+        position: [K; 2],
+        velocity: [K; 2],
+        steps: usize,
+        stride: usize,
+    ) -> Vec<[K; 2]> {
+        let m = &self.math_space;
+        let half = (K::one() + K::one()).inv();
+        let stride = stride.max(1);
+        let (mut p, mut v) = (position, velocity);
+        let mut a = self.field_at(&p);
+        let mut samples = Vec::with_capacity(steps / stride);
+        for step in 1..=steps {
+            v = m.add(&v, &m.mul(&half, &a));
+            p = m.add(&p, &v);
+            a = self.field_at(&p);
+            v = m.add(&v, &m.mul(&half, &a));
+            if step % stride == 0 {
+                samples.push(p.clone());
+            }
+        }
+        samples
+    }
+
+    /// Gravitational force element `j` alone exerts on element `i`, zero if they are at the
+    /// same position.
+    pub fn pair_force(&self, i: usize, j: usize) -> [K; 2] {
+        let (e1, e2) = (&self.elements[i], &self.elements[j]);
+        let m = &self.math_space;
+        if m.distance(&e1.position_vector, &e2.position_vector).is_zero() {
+            return [K::zero(), K::zero()];
+        }
+        m.mul(&e1.mass, &self.pull(e2, &e1.position_vector))
+    }
+
+    /// Gravitational potential `-Σ G m_j / r_ij` at the position of element `i`,
+    /// caused by all other elements.
+    pub fn potential(&self, i: usize) -> K {
+        let m = &self.math_space;
+        let e1 = &self.elements[i];
+        self.elements
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .fold(K::zero(), |potential, (_, e2)| {
+                let distance = m.distance(&e1.position_vector, &e2.position_vector);
+                if distance.is_zero() {
+                    potential
+                } else {
+                    potential
+                        - self.gravitational_constant.clone()
+                            * e2.mass.clone()
+                            * self.inverse_distance(distance)
+                }
+            })
+    }
+
+    pub fn kinetic_energy(&self) -> K {
+        let m = &self.math_space;
+        let half = (K::one() + K::one()).inv();
+        let origin = [K::zero(), K::zero()];
+        self.elements.iter().fold(K::zero(), |energy, e| {
+            let speed = m.distance(&origin, &e.direction_vector);
+            energy + half.clone() * e.mass.clone() * speed.clone() * speed
+        })
+    }
+
+    /// Pairwise gravitational potential energy, summed directly in O(n²).
+    pub fn potential_energy(&self) -> K {
+        let half = (K::one() + K::one()).inv();
+        (0..self.elements.len()).fold(K::zero(), |energy, i| {
+            energy + half.clone() * self.elements[i].mass.clone() * self.potential(i)
+        })
+    }
+
+    pub fn total_energy(&self) -> K {
+        self.kinetic_energy() + self.potential_energy()
+    }
+
+    /// Mass-weighted mean position, the origin if there is no mass.
+    pub fn center_of_mass(&self) -> [K; 2] {
+        let m = &self.math_space;
+        let (total, weighted) = self.elements.iter().fold(
+            (K::zero(), [K::zero(), K::zero()]),
+            |(total, weighted), e| {
+                (total + e.mass.clone(), m.add(&weighted, &m.mul(&e.mass, &e.position_vector)))
+            },
+        );
+        if total.is_zero() {
+            return [K::zero(), K::zero()];
+        }
+        m.mul(&total.inv(), &weighted)
+    }
+
+    /// Total mass within each of `radii` of the center of mass, in the order of `radii`.
+    /// Elements are sorted by distance once, each radius is then a binary search.
+    pub fn enclosed_mass_profile(&self, radii: &[K]) -> Vec<K> {
+        let shells = self.shells();
+        let mut cumulative = Vec::with_capacity(shells.len());
+        shells.iter().fold(K::zero(), |sum, (_, mass)| {
+            let sum = sum + mass.clone();
+            cumulative.push(sum.clone());
+            sum
+        });
+        radii
+            .iter()
+            .map(|r| match shells.partition_point(|(d, _)| d <= r) {
+                0 => K::zero(),
+                n => cumulative[n - 1].clone(),
+            })
+            .collect()
+    }
+
+    /// Smallest distance from the center of mass within which at least half of the total
+    /// mass lies, zero without elements.
+    pub fn half_mass_radius(&self) -> K {
+        self.mass_radius((K::one() + K::one()).inv())
+    }
+
+    /// Smallest distance from the center of mass within which at least `fraction` of the
+    /// total mass lies, zero without elements.
+    pub fn mass_radius(&self, fraction: K) -> K {
+        let shells = self.shells();
+        let target = shells.iter().fold(K::zero(), |sum, (_, mass)| sum + mass.clone()) * fraction;
+        let mut enclosed = K::zero();
+        for (distance, mass) in shells {
+            enclosed = enclosed + mass;
+            if enclosed >= target {
+                return distance;
+            }
+        }
+        K::zero()
+    }
+
+    /// Distance from the center of mass and mass of every element, nearest first.
+    fn shells(&self) -> Vec<(K, K)> {
+        let m = &self.math_space;
+        let center = self.center_of_mass();
+        let mut shells: Vec<(K, K)> = self
+            .elements
+            .iter()
+            .map(|e| (m.distance(&e.position_vector, &center), e.mass.clone()))
+            .collect();
+        shells.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        shells
+    }
+
+    /// Indices of elements whose specific energy `v²/2 + potential` is positive.
+    pub fn unbound_indices(&self) -> Vec<usize> {
+        let m = &self.math_space;
+        let half = (K::one() + K::one()).inv();
+        let origin = [K::zero(), K::zero()];
+        (0..self.elements.len())
+            .filter(|&i| {
+                let speed = m.distance(&origin, &self.elements[i].direction_vector);
+                half.clone() * speed.clone() * speed + self.potential(i) > K::zero()
+            })
+            .collect()
+    }
+
+    /// Number of elements within `radius` of `point`, including those exactly on the circle.
+    /// Zero for a negative radius.
+    pub fn count_within(&self, point: &[K; 2], radius: &K) -> usize {
+        if *radius < K::zero() {
+            return 0;
+        }
+        let m = &self.math_space;
+        let limit = radius.clone() * radius.clone();
+        self.elements
+            .iter()
+            .filter(|e| m.distance_squared(&e.position_vector, point) <= limit)
+            .count()
+    }
+
+    /// Moves every element by `offset`, velocities are unchanged.
+    pub fn translate(&mut self, offset: &[K; 2]) {
+        let m = &self.math_space;
+        self.elements
+            .iter_mut()
+            .for_each(|e| e.position_vector = m.add(&e.position_vector, offset));
+    }
+
+    /// Removes elements according to `policy` until at most `max` are left. Protected
+    /// elements and accretors are never removed, so more may be left if there are many.
+    pub fn enforce_max_elements<R: Rng>(&mut self, max: usize, policy: CapPolicy, rng: &mut R) {
+        let len = self.elements.len();
+        if len <= max {
+            return;
+        }
+        let is_accretor = |id: u32| self.accretors.iter().any(|(other, _)| *other == id);
+        let mut order: Vec<usize> = (0..len)
+            .filter(|&i| !self.elements[i].protected && !is_accretor(self.elements[i].id))
+            .collect();
+        let excess = (len - max).min(order.len());
+        if excess == 0 {
+            return;
+        }
+        let victims: Vec<usize> = match policy {
+            CapPolicy::Oldest => {
+                order.sort_by_key(|&i| self.elements[i].birth_tick);
+                order.truncate(excess);
+                order
+            }
+            CapPolicy::Lightest | CapPolicy::MergeLightest => {
+                order.sort_by(|&a, &b| {
+                    self.elements[a]
+                        .mass
+                        .partial_cmp(&self.elements[b].mass)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                order.truncate(excess);
+                order
+            }
+            CapPolicy::Random => rand::seq::index::sample(rng, order.len(), excess)
+                .into_iter()
+                .map(|k| order[k])
+                .collect(),
+        };
+
+        let mut removed = vec![false; len];
+        victims.iter().for_each(|&i| removed[i] = true);
+        let mut merged = 0;
+        if policy == CapPolicy::MergeLightest {
+            let m = &self.math_space;
+            for &i in victims.iter() {
+                let position = &self.elements[i].position_vector;
+                let nearest = (0..len).filter(|&j| !removed[j]).min_by(|&a, &b| {
+                    let da = m.distance(position, &self.elements[a].position_vector);
+                    let db = m.distance(position, &self.elements[b].position_vector);
+                    da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+                });
+                if let Some(j) = nearest {
+                    self.elements[j] = self.merge(&self.elements[j], &self.elements[i]);
+                    merged += 1;
+                }
+            }
+        }
+        let mut index = 0;
+        self.elements.retain(|_| {
+            index += 1;
+            !removed[index - 1]
+        });
+        self.pending_report.merges += merged as u32;
+        self.pending_report.particles_removed += (excess - merged) as u32;
+    }
+
+    #[doc(hidden)]
+    #[allow(dead_code)]
     pub fn print(&self) {
         self.elements.iter().for_each(|e| {
             println!(
@@ -247,31 +1655,41 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
 impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
     pub fn tick(&mut self) {
        // console_log!("Tick ");
-        let m = &self.math_space;
+        self.report = std::mem::take(&mut self.pending_report);
+        self.escapes.clear();
+        let cull_origin = self.cull_origin();
+        let mut center = match self.cull_center {
+            CullCenter::CenterOfMass => Some(cull_origin.clone()),
+            CullCenter::Fixed(_) => None,
+        };
         let mut elements = self.elements.clone();
 
         for i in 0..elements.len() {
-            //Remove elements that are too far away
+            //Remove elements that match a cull rule
             match elements[i].status {
                 ObjectStatus::Default => {
                     //Only remove elements that have not been removed or merged
-                    if m.distance(&[K::zero(), K::zero()], &elements[i].position_vector)
-                        > self.radius
-                    {
+                    if self.is_culled(&elements[i], &cull_origin) {
                         //  println!("Deleting {:?}", elements[i]);
-                        elements[i].status = ObjectStatus::Deleted
+                        elements[i].status = ObjectStatus::Deleted;
+                        let center = center.get_or_insert_with(|| self.center_of_mass());
+                        let offset = self.math_space.sub(&elements[i].position_vector, center);
+                        self.escapes.push((offset, elements[i].mass.clone()));
 
+                    } else if self.absorb(&elements[i]) {
+                        elements[i].status = ObjectStatus::Deleted
                     } else {
                         // If status is still default, check merges
-                        checkMerge(self, &mut elements, i);
+                        check_merge(self, &mut elements, i);
                     }
                 }
                 // If particle A was merged into B, check if other particles would have merged into A. If yes, also merge them into B
-                ObjectStatus::MergedInto(k) => checkMerge(self, &mut elements, i),
+                ObjectStatus::MergedInto(_) => check_merge(self, &mut elements, i),
                 _ => {}
             }
             // {}
         }
+        let nan = self.quarantine_non_finite(&mut elements);
 
         // elements = elements
         //     .iter()
@@ -280,20 +1698,19 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
         //         _ => e.clone(),
         //     })
         //     .collect();
-        elements.retain(|e| e.status == ObjectStatus::Default);
+        self.compact(&mut elements);
 
-        fn checkMerge<L: Field + PartialOrd, M: MathSpace<L>>(
+        fn check_merge<L: Field + PartialOrd, M: MathSpace<L>>(
             phys: &PhysicsSpace<L, M>,
-            elements: &mut Vec<PhysicsObject<L>>,
+            elements: &mut [PhysicsObject<L>],
             i: usize,
         ) {
-            let m = &phys.math_space;
             for j in i + 1..phys.elements.len() {
                 // Merge elements that are too close together
                 // Always merge j into i. Update the values of i and mark j as Merged(into)
 
-                if m.distance(&elements[i].position_vector, &elements[j].position_vector)
-                    < phys.epsilon
+                if elements[j].status != ObjectStatus::Deleted
+                    && phys.should_merge(&elements[i], &elements[j])
                 {
                     match elements[i].status {
                         ObjectStatus::Default => {
@@ -314,9 +1731,629 @@ impl<K: Field + PartialOrd, S: MathSpace<K>> PhysicsSpace<K, S> {
         }
 
 self.elements = elements;
-        self.elements = self.elements
+        if self.gravity_enabled {
+            let sources = self.elements.iter().filter(|e| self.attracts(e)).count();
+            self.report.force_sources = sources as u32;
+        }
+        if let Some(sph) = self.sph.clone() {
+            // Kick velocities before the drift, so the pressure acts within this tick
+            let m = &self.math_space;
+            let kicks = self.sph_accelerations(&sph);
+            self.elements
+                .iter_mut()
+                .zip(kicks)
+                .for_each(|(e, a)| e.direction_vector = m.add(&e.direction_vector, &a));
+        }
+        let pairs = match &self.regularization {
+            Some((_, distance)) => self.close_pairs(distance),
+            None => Vec::new(),
+        };
+        let start: Vec<[K; 2]> = if self.accretors.is_empty() {
+            Vec::new()
+        } else {
+            self.elements.iter().map(|e| e.position_vector.clone()).collect()
+        };
+        let mut paired = vec![false; self.elements.len()];
+        pairs.iter().for_each(|&(i, j)| {
+            paired[i] = true;
+            paired[j] = true;
+        });
+        let mut largest = [K::zero(), K::zero()];
+        let mut next: Vec<PhysicsObject<K>> = self
+            .elements
+            .iter()
+            .zip(&paired)
+            .enumerate()
+            .map(|(i, (e1, &paired))| match self.integrator {
+                _ if paired => e1.clone(),
+                Integrator::Hybrid => {
+                    let next = self.leapfrog_integration(i, e1);
+                    keep_larger(&mut largest, &next.acceleration_vector);
+                    next
+                }
+                Integrator::KickDriftKick => self.kick_drift(e1),
+            })
+            .collect();
+        if let Some((substeps, _)) = &self.regularization {
+            for &(i, j) in pairs.iter() {
+                let (e1, e2) = self.integrate_pair(i, j, *substeps);
+                keep_larger(&mut largest, &e1.acceleration_vector);
+                keep_larger(&mut largest, &e2.acceleration_vector);
+                next[i] = e1;
+                next[j] = e2;
+            }
+        }
+        if self.integrator == Integrator::KickDriftKick {
+            // Evaluate the closing kick with every element at its drifted position and time
+            self.elements = next;
+            let time = self.tick_count as f64 + 1.0;
+            let half = (K::one() + K::one()).inv();
+            let accelerations: Vec<Option<[K; 2]>> = self
+                .elements
+                .iter()
+                .zip(&paired)
+                .enumerate()
+                .map(|(i, (e, &paired))| {
+                    if paired {
+                        None
+                    } else {
+                        Some(self.acceleration(i, e, &e.position_vector, time))
+                    }
+                })
+                .collect();
+            let m = &self.math_space;
+            for (e, a) in self.elements.iter_mut().zip(accelerations) {
+                if let Some(a) = a {
+                    keep_larger(&mut largest, &a);
+                    e.direction_vector = m.add(&e.direction_vector, &m.mul(&half, &a));
+                    e.acceleration_vector = a;
+                }
+            }
+            next = std::mem::take(&mut self.elements);
+        }
+        if self.boundary_mode == BoundaryMode::Clamp {
+            next.iter_mut().for_each(|e| self.clamp_to_boundary(e, &cull_origin));
+        }
+        if !self.accretors.is_empty() {
+            self.sweep_accretors(&start, &mut next);
+        }
+        if let Some(id) = nan.or(self.quarantine_non_finite(&mut next)) {
+            self.nan_report = Some(id);
+        }
+        self.compact(&mut next);
+        self.elements = next;
+        self.regularized_pairs = pairs.len();
+        self.max_acceleration = largest;
+        self.tick_count += 1;
+        self.report.simulation_time = self.tick_count as f64;
+    }
+}
+
+impl<S: MathSpace<f64>> PhysicsSpace<f64, S> {
+    /// How far this space has diverged from `other`, matching elements by id, so their order
+    /// does not matter.
+    pub fn compare<T: MathSpace<f64>>(&self, other: &PhysicsSpace<f64, T>) -> Divergence {
+        let others: HashMap<u32, &PhysicsObject<f64>> =
+            other.elements.iter().map(|e| (e.id, e)).collect();
+        let mut report = Divergence {
+            count_difference: self.elements.len() as i32 - other.elements.len() as i32,
+            ..Divergence::default()
+        };
+        let (mut position_sum, mut velocity_sum, mut matched) = (0.0, 0.0, 0);
+        for e in self.elements.iter() {
+            let o = match others.get(&e.id) {
+                Some(o) => o,
+                None => continue,
+            };
+            let dp = (e.position_vector[0] - o.position_vector[0])
+                .hypot(e.position_vector[1] - o.position_vector[1]);
+            let dv = (e.direction_vector[0] - o.direction_vector[0])
+                .hypot(e.direction_vector[1] - o.direction_vector[1]);
+            position_sum += dp * dp;
+            velocity_sum += dv * dv;
+            report.max_position = report.max_position.max(dp);
+            report.max_velocity = report.max_velocity.max(dv);
+            matched += 1;
+        }
+        report.unmatched = (self.elements.len() + other.elements.len() - 2 * matched) as u32;
+        if matched > 0 {
+            report.rms_position = (position_sum / matched as f64).sqrt();
+            report.rms_velocity = (velocity_sum / matched as f64).sqrt();
+        }
+        let mass = |elements: &[PhysicsObject<f64>]| elements.iter().map(|e| e.mass).sum::<f64>();
+        report.mass_difference = mass(&self.elements) - mass(&other.elements);
+        report
+    }
+
+    /// Smallest box containing every element, `None` if there are no elements.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.elements
+            .iter()
+            .map(|e| Bounds::new(e.position_vector[0], e.position_vector[1], 0.0, 0.0))
+            .fold(None, |bounds: Option<Bounds>, b| {
+                Some(bounds.map_or(b, |bounds| bounds.union(&b)))
+            })
+    }
+
+    /// Mass-weighted mean velocity of the elements inside `region`, zero if there are none.
+    pub fn bulk_velocity_in(&self, region: &Bounds) -> [f64; 2] {
+        let [_, _, vx, vy, _] = self.region_stats(region);
+        [vx, vy]
+    }
+
+    /// `[count, mass, vx, vy, kinetic_energy]` of the elements inside `region`, the velocity
+    /// being their mass-weighted mean, or zero if there are none.
+    pub fn region_stats(&self, region: &Bounds) -> [f64; 5] {
+        let [count, mass, px, py, kinetic] = self
+            .elements
+            .iter()
+            .filter(|e| region.contains(e.position_vector[0], e.position_vector[1]))
+            .fold([0.0; 5], |[count, mass, px, py, kinetic], e| {
+                let v = e.direction_vector;
+                [
+                    count + 1.0,
+                    mass + e.mass,
+                    px + e.mass * v[0],
+                    py + e.mass * v[1],
+                    kinetic + 0.5 * e.mass * (v[0] * v[0] + v[1] * v[1]),
+                ]
+            });
+        if mass == 0.0 {
+            return [count, mass, 0.0, 0.0, kinetic];
+        }
+        [count, mass, px / mass, py / mass, kinetic]
+    }
+
+    /// `[count, mass, x, y, vx, vy, velocity dispersion, rms radius]` of the elements with
+    /// origin code `origin`, in a single pass. Position and velocity are mass-weighted means,
+    /// the dispersion and radius are mass-weighted RMS deviations from them. All but the count
+    /// are zero if the group has no mass.
+    pub fn group_stats(&self, origin: u16) -> [f64; 8] {
+        // Count, mass, then mass-weighted x, y, vx, vy, r² and v²
+        let mut sums = [0.0; 8];
+        for e in self.elements.iter().filter(|e| e.origin == origin) {
+            let (p, v, m) = (&e.position_vector, &e.direction_vector, e.mass);
+            sums[0] += 1.0;
+            sums[1] += m;
+            sums[2] += m * p[0];
+            sums[3] += m * p[1];
+            sums[4] += m * v[0];
+            sums[5] += m * v[1];
+            sums[6] += m * (p[0] * p[0] + p[1] * p[1]);
+            sums[7] += m * (v[0] * v[0] + v[1] * v[1]);
+        }
+        let (count, mass) = (sums[0], sums[1]);
+        if mass == 0.0 {
+            return [count, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        }
+        let [x, y, vx, vy] = [sums[2] / mass, sums[3] / mass, sums[4] / mass, sums[5] / mass];
+        let dispersion = (sums[7] / mass - vx * vx - vy * vy).max(0.0).sqrt();
+        let radius = (sums[6] / mass - x * x - y * y).max(0.0).sqrt();
+        [count, mass, x, y, vx, vy, dispersion, radius]
+    }
+
+    /// Mass-weighted mean velocity in each cell of an `nx` x `ny` grid over `bounds()`, row by
+    /// row starting at the lower corner, relative to the velocity `frame`. Empty cells are zero.
+    pub fn velocity_grid(&self, nx: usize, ny: usize, frame: &[f64; 2]) -> Vec<[f64; 2]> {
+        self.bin_over_bounds(nx, ny)
+            .into_iter()
+            .map(|(mass, momentum)| {
+                if mass > 0.0 {
+                    [momentum[0] / mass - frame[0], momentum[1] / mass - frame[1]]
+                } else {
+                    [0.0, 0.0]
+                }
+            })
+            .collect()
+    }
+
+    /// Curl `∂vy/∂x - ∂vx/∂y` of `velocity_grid`, by central differences and one-sided
+    /// differences at the edges.
+    pub fn vorticity_grid(&self, nx: usize, ny: usize) -> Vec<f64> {
+        let (dx, dy) = self.grid_derivatives(nx, ny);
+        dx.iter().zip(dy.iter()).map(|(dx, dy)| dx[1] - dy[0]).collect()
+    }
+
+    /// Divergence `∂vx/∂x + ∂vy/∂y` of `velocity_grid`, differenced like `vorticity_grid`.
+    pub fn divergence_grid(&self, nx: usize, ny: usize) -> Vec<f64> {
+        let (dx, dy) = self.grid_derivatives(nx, ny);
+        dx.iter().zip(dy.iter()).map(|(dx, dy)| dx[0] + dy[1]).collect()
+    }
+
+    /// Total mass and momentum per cell of an `nx` x `ny` grid over the bounding box.
+    /// Elements on the upper edges go into the last row or column.
+    fn bin_over_bounds(&self, nx: usize, ny: usize) -> Vec<(f64, [f64; 2])> {
+        let mut cells = vec![(0.0, [0.0, 0.0]); nx * ny];
+        let bounds = match self.bounds() {
+            Some(bounds) if nx > 0 && ny > 0 => bounds,
+            _ => return cells,
+        };
+        let cell = |offset: f64, size: f64, n: usize| {
+            if size > 0.0 {
+                ((offset / size * n as f64) as usize).min(n - 1)
+            } else {
+                0
+            }
+        };
+        for e in self.elements.iter() {
+            let i = cell(e.position_vector[0] - bounds.x, bounds.width, nx);
+            let j = cell(e.position_vector[1] - bounds.y, bounds.height, ny);
+            let (mass, momentum) = &mut cells[j * nx + i];
+            *mass += e.mass;
+            momentum[0] += e.mass * e.direction_vector[0];
+            momentum[1] += e.mass * e.direction_vector[1];
+        }
+        cells
+    }
+
+    /// Partial derivatives of the velocity grid along x and along y, per cell.
+    fn grid_derivatives(&self, nx: usize, ny: usize) -> (Vec<[f64; 2]>, Vec<[f64; 2]>) {
+        let velocities = self.velocity_grid(nx, ny, &[0.0, 0.0]);
+        let (width, height) = self.bounds().map_or((0.0, 0.0), |b| (b.width, b.height));
+        let (cell_width, cell_height) = (width / nx as f64, height / ny as f64);
+        let difference = |a: [f64; 2], b: [f64; 2], distance: f64| {
+            if distance > 0.0 {
+                [(a[0] - b[0]) / distance, (a[1] - b[1]) / distance]
+            } else {
+                [0.0, 0.0]
+            }
+        };
+        // Neighbors one cell before and after, clamped to the grid
+        let span = |k: usize, n: usize| (k.saturating_sub(1), (k + 1).min(n - 1));
+        let mut dx = Vec::with_capacity(velocities.len());
+        let mut dy = Vec::with_capacity(velocities.len());
+        for j in 0..ny {
+            for i in 0..nx {
+                let (left, right) = span(i, nx);
+                let (below, above) = span(j, ny);
+                dx.push(difference(
+                    velocities[j * nx + right],
+                    velocities[j * nx + left],
+                    (right - left) as f64 * cell_width,
+                ));
+                dy.push(difference(
+                    velocities[above * nx + i],
+                    velocities[below * nx + i],
+                    (above - below) as f64 * cell_height,
+                ));
+            }
+        }
+        (dx, dy)
+    }
+
+    /// Rotates positions about `pivot` and velocities about the origin by `angle` radians.
+    pub fn rotate(&mut self, angle: f64, pivot: &[f64; 2]) {
+        let (sin, cos) = angle.sin_cos();
+        let turn = |v: [f64; 2]| [cos * v[0] - sin * v[1], sin * v[0] + cos * v[1]];
+        self.elements.iter_mut().for_each(|e| {
+            let r = turn([e.position_vector[0] - pivot[0], e.position_vector[1] - pivot[1]]);
+            e.position_vector = [pivot[0] + r[0], pivot[1] + r[1]];
+            e.direction_vector = turn(e.direction_vector);
+            e.acceleration_vector = turn(e.acceleration_vector);
+        });
+    }
+
+    /// Adds a velocity of `speed * exp(-falloff * r)` directed away from `center` to every
+    /// element, `r` being its distance from `center`. Elements exactly at `center` have no
+    /// direction and are left alone.
+    pub fn explode(&mut self, center: &[f64; 2], speed: f64, falloff: f64) {
+        self.elements.iter_mut().for_each(|e| {
+            let offset = [e.position_vector[0] - center[0], e.position_vector[1] - center[1]];
+            let r = offset[0].hypot(offset[1]);
+            if r > 0.0 {
+                let kick = speed * (-falloff * r).exp() / r;
+                e.direction_vector[0] += kick * offset[0];
+                e.direction_vector[1] += kick * offset[1];
+            }
+        });
+    }
+
+    /// Replaces the element at `index` by `parts` elements of equal mass, scattered
+    /// uniformly over a disk of `scatter_radius` around its position.
+    ///
+    /// Every part inherits the parent's velocity plus a random isotropic kick. The kicks
+    /// are scaled so that the internal kinetic energy of the parts equals
+    /// `energy_fraction` of the parent's binding energy `G m² / scatter_radius`.
+    /// Offsets and kicks are corrected by their mean, so mass, momentum and the center
+    /// of mass are conserved.
+    pub fn split<R: Rng>(
+        &mut self,
+        index: usize,
+        parts: usize,
+        scatter_radius: f64,
+        energy_fraction: f64,
+        rng: &mut R,
+    ) -> bool {
+        if index >= self.elements.len() || parts < 2 {
+            return false;
+        }
+        let parent = self.elements[index].clone();
+        let part_mass = parent.mass / parts as f64;
+
+        let mut offsets: Vec<[f64; 2]> = (0..parts)
+            .map(|_| {
+                let r = scatter_radius * rng.gen::<f64>().sqrt();
+                let dir: [f64; 2] = rng.sample(UnitCircle);
+                [r * dir[0], r * dir[1]]
+            })
+            .collect();
+        let mut kicks: Vec<[f64; 2]> = (0..parts).map(|_| rng.sample(UnitCircle)).collect();
+        remove_mean(&mut offsets);
+        remove_mean(&mut kicks);
+
+        let internal_energy: f64 = kicks
             .iter()
-            .map(|e1| self.leapfrog_integration(e1))
+            .map(|k| 0.5 * part_mass * (k[0] * k[0] + k[1] * k[1]))
+            .sum();
+        let binding_energy =
+            self.gravitational_constant * parent.mass * parent.mass / scatter_radius;
+        let scale = if internal_energy > 0.0 && binding_energy.is_finite() {
+            (energy_fraction * binding_energy / internal_energy).sqrt()
+        } else {
+            0.0
+        };
+
+        let mut pieces = offsets.iter().zip(kicks.iter()).map(|(o, k)| PhysicsObject {
+            position_vector: [parent.position_vector[0] + o[0], parent.position_vector[1] + o[1]],
+            direction_vector: [
+                parent.direction_vector[0] + scale * k[0],
+                parent.direction_vector[1] + scale * k[1],
+            ],
+            acceleration_vector: parent.acceleration_vector,
+            mass: part_mass,
+            id: parent.id,
+            birth_tick: parent.birth_tick,
+            origin: parent.origin,
+            protected: parent.protected,
+            status: ObjectStatus::Default,
+        });
+        self.elements[index] = pieces.next().unwrap();
+        for mut piece in pieces {
+            piece.id = self.new_id();
+            piece.birth_tick = self.tick_count;
+            piece.origin = ORIGIN_SPLIT;
+            self.elements.push(piece);
+            self.pending_report.particles_added += 1;
+        }
+        true
+    }
+
+    /// Adds Gaussian velocity kicks with standard deviation `sigma`, shifted so that they
+    /// carry no net momentum.
+    pub fn heat<R: Rng>(&mut self, sigma: f64, rng: &mut R) {
+        let mut kicks: Vec<[f64; 2]> = self
+            .elements
+            .iter()
+            .map(|_| [rng.sample(StandardNormal), rng.sample(StandardNormal)])
             .collect();
+        let total_mass: f64 = self.elements.iter().map(|e| e.mass).sum();
+        if total_mass != 0.0 {
+            let mean = self.elements.iter().zip(&kicks).fold([0.0, 0.0], |acc, (e, k)| {
+                [acc[0] + e.mass * k[0] / total_mass, acc[1] + e.mass * k[1] / total_mass]
+            });
+            kicks.iter_mut().for_each(|k| {
+                k[0] -= mean[0];
+                k[1] -= mean[1];
+            });
+        }
+        for (e, k) in self.elements.iter_mut().zip(kicks) {
+            e.direction_vector[0] += sigma * k[0];
+            e.direction_vector[1] += sigma * k[1];
+        }
+    }
+
+    /// Stand-in for the two-body relaxation that merged and massive elements suppress. Every
+    /// element is paired with its nearest neighbor, unless either was already paired, and the
+    /// relative velocity of each pair is turned by a random angle. Positions and the speed of
+    /// the pair relative to its center of mass stay the same, so the momentum and energy of
+    /// every pair are conserved exactly.
+    ///
+    /// The angle is Gaussian with standard deviation `coefficient * 2 atan(G M / (d v²))`, the
+    /// deflection of a Rutherford encounter with impact parameter `d`, the pair's separation,
+    /// `M` its mass and `v` its relative speed.
+    pub fn scatter<R: Rng>(&mut self, coefficient: f64, rng: &mut R) {
+        let n = self.elements.len();
+        let mut paired = vec![false; n];
+        for i in 0..n {
+            if paired[i] {
+                continue;
+            }
+            let p = self.elements[i].position_vector;
+            let nearest = (0..n)
+                .filter(|&j| j != i)
+                .map(|j| {
+                    let q = &self.elements[j].position_vector;
+                    (j, (q[0] - p[0]).hypot(q[1] - p[1]))
+                })
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+            let (j, d) = match nearest {
+                Some((j, d)) if !paired[j] && d > 0.0 => (j, d),
+                _ => continue,
+            };
+            paired[i] = true;
+            paired[j] = true;
+            let (a, b) = (&self.elements[i], &self.elements[j]);
+            let total = a.mass + b.mass;
+            let relative = [
+                a.direction_vector[0] - b.direction_vector[0],
+                a.direction_vector[1] - b.direction_vector[1],
+            ];
+            let speed2 = relative[0] * relative[0] + relative[1] * relative[1];
+            if speed2 == 0.0 || total == 0.0 {
+                continue;
+            }
+            let deflection = 2.0 * (self.gravitational_constant * total / (d * speed2)).atan();
+            let angle = coefficient * deflection * rng.sample::<f64, _>(StandardNormal);
+            let (sin, cos) = angle.sin_cos();
+            let turned = [
+                cos * relative[0] - sin * relative[1],
+                sin * relative[0] + cos * relative[1],
+            ];
+            // Velocities of the two around the pair's center of mass scale with the other mass
+            let (wa, wb) = (b.mass / total, a.mass / total);
+            for axis in 0..2 {
+                let change = turned[axis] - relative[axis];
+                self.elements[i].direction_vector[axis] += wa * change;
+                self.elements[j].direction_vector[axis] -= wb * change;
+            }
+        }
+    }
+
+    /// `[kinetic energy, px, py, angular momentum about the origin, center of mass x, y]`,
+    /// gathered in a single pass. The center of mass is the origin if there is no mass.
+    pub fn conserved_sums(&self) -> [f64; 6] {
+        let mut sums = [0.0; 7];
+        for e in self.elements.iter() {
+            let (p, v, m) = (&e.position_vector, &e.direction_vector, e.mass);
+            sums[0] += 0.5 * m * (v[0] * v[0] + v[1] * v[1]);
+            sums[1] += m * v[0];
+            sums[2] += m * v[1];
+            sums[3] += m * (p[0] * v[1] - p[1] * v[0]);
+            sums[4] += m * p[0];
+            sums[5] += m * p[1];
+            sums[6] += m;
+        }
+        let total = if sums[6] == 0.0 { 1.0 } else { sums[6] };
+        [sums[0], sums[1], sums[2], sums[3], sums[4] / total, sums[5] / total]
+    }
+
+    /// Kinetic energy `[total, bulk translation, rotation, random]` in a single pass.
+    /// Translation is that of the center of mass velocity. Rotation is that of a rigid
+    /// rotation about the center of mass with the same angular momentum, i.e. with the
+    /// angular velocity L / I. Random motion is the remainder.
+    pub fn energy_decomposition(&self) -> [f64; 4] {
+        // Mass, mass-weighted x, y, vx, vy, x² + y², v², x·vy - y·vx
+        let mut sums = [0.0; 8];
+        for e in self.elements.iter() {
+            let (p, v, m) = (&e.position_vector, &e.direction_vector, e.mass);
+            sums[0] += m;
+            sums[1] += m * p[0];
+            sums[2] += m * p[1];
+            sums[3] += m * v[0];
+            sums[4] += m * v[1];
+            sums[5] += m * (p[0] * p[0] + p[1] * p[1]);
+            sums[6] += m * (v[0] * v[0] + v[1] * v[1]);
+            sums[7] += m * (p[0] * v[1] - p[1] * v[0]);
+        }
+        let total = 0.5 * sums[6];
+        if sums[0] == 0.0 {
+            return [total, 0.0, 0.0, total];
+        }
+        let mass = sums[0];
+        let bulk = 0.5 * (sums[3] * sums[3] + sums[4] * sums[4]) / mass;
+        // Moment of inertia and angular momentum about the center of mass
+        let inertia = sums[5] - (sums[1] * sums[1] + sums[2] * sums[2]) / mass;
+        let l = sums[7] - (sums[1] * sums[4] - sums[2] * sums[3]) / mass;
+        let rotation = if inertia > 0.0 { 0.5 * l * l / inertia } else { 0.0 };
+        [total, bulk, rotation, (total - bulk - rotation).max(0.0)]
+    }
+
+    /// Two-body `[specific energy, semi-major axis, eccentricity]` of every element about
+    /// element `central`, in element order, from the position and velocity relative to it and
+    /// `G` times both masses. Unbound orbits have a negative semi-major axis and an
+    /// eccentricity above one. The row of `central` itself is NaN.
+    pub fn orbital_elements_about(&self, central: usize) -> Vec<[f64; 3]> {
+        let primary = &self.elements[central];
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, e)| {
+                if i == central {
+                    return [f64::NAN; 3];
+                }
+                let mu = self.gravitational_constant * (primary.mass + e.mass);
+                let r = [
+                    e.position_vector[0] - primary.position_vector[0],
+                    e.position_vector[1] - primary.position_vector[1],
+                ];
+                let v = [
+                    e.direction_vector[0] - primary.direction_vector[0],
+                    e.direction_vector[1] - primary.direction_vector[1],
+                ];
+                let energy = 0.5 * (v[0] * v[0] + v[1] * v[1]) - mu / r[0].hypot(r[1]);
+                let h = r[0] * v[1] - r[1] * v[0];
+                let eccentricity = (1.0 + 2.0 * energy * h * h / (mu * mu)).max(0.0).sqrt();
+                [energy, -mu / (2.0 * energy), eccentricity]
+            })
+            .collect()
+    }
+
+    /// Score of every element under `importance`, in element order.
+    pub fn importance(&self, importance: Importance) -> Vec<f64> {
+        self.elements
+            .iter()
+            .map(|e| {
+                let speed = e.direction_vector[0].hypot(e.direction_vector[1]);
+                match importance {
+                    Importance::Momentum => speed * e.mass,
+                    Importance::Speed => speed,
+                    Importance::Mass => e.mass,
+                    Importance::Acceleration => {
+                        e.acceleration_vector[0].hypot(e.acceleration_vector[1])
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Adds a Gaussian velocity kick with standard deviation `amplitude` per component to
+    /// every element, like a heat bath.
+    pub fn add_thermal_noise<R: Rng>(&mut self, amplitude: f64, rng: &mut R) {
+        self.elements.iter_mut().for_each(|e| {
+            let kick: [f64; 2] = [rng.sample(StandardNormal), rng.sample(StandardNormal)];
+            e.direction_vector[0] += amplitude * kick[0];
+            e.direction_vector[1] += amplitude * kick[1];
+        });
+    }
+}
+
+fn remove_mean(vectors: &mut [[f64; 2]]) {
+    let n = vectors.len() as f64;
+    let mean = vectors
+        .iter()
+        .fold([0.0, 0.0], |acc, v| [acc[0] + v[0] / n, acc[1] + v[1] / n]);
+    vectors.iter_mut().for_each(|v| {
+        v[0] -= mean[0];
+        v[1] -= mean[1];
+    });
+}
+
+/// `numerator / denominator` in any field, built from ones by doubling.
+fn ratio<K: Field>(numerator: u32, denominator: u32) -> K {
+    let integer = |n: u32| {
+        (0..32 - n.leading_zeros()).rev().fold(K::zero(), |k, bit| {
+            let k = k.clone() + k;
+            if n >> bit & 1 == 1 {
+                k + K::one()
+            } else {
+                k
+            }
+        })
+    };
+    integer(numerator) * integer(denominator).inv()
+}
+
+/// Squared distance of the origin from the segment between `from` and `to`.
+fn closest_approach_squared<K: Field + PartialOrd>(from: &[K; 2], to: &[K; 2]) -> K {
+    let dot = |a: &[K; 2], b: &[K; 2]| a[0].clone() * b[0].clone() + a[1].clone() * b[1].clone();
+    let step = [to[0].clone() - from[0].clone(), to[1].clone() - from[1].clone()];
+    let length = dot(&step, &step);
+    let along = K::zero() - dot(from, &step);
+    let closest = if along <= K::zero() {
+        from.clone()
+    } else if along >= length {
+        to.clone()
+    } else {
+        let t = along * length.inv();
+        [from[0].clone() + t.clone() * step[0].clone(), from[1].clone() + t * step[1].clone()]
+    };
+    dot(&closest, &closest)
+}
+
+/// Replaces `largest` by `a` if `a` is longer. NaN never replaces it.
+fn keep_larger<K: Field + PartialOrd>(largest: &mut [K; 2], a: &[K; 2]) {
+    let squared = |v: &[K; 2]| v[0].clone() * v[0].clone() + v[1].clone() * v[1].clone();
+    if squared(a) > squared(largest) {
+        *largest = a.clone();
     }
 }
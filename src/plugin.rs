@@ -0,0 +1,175 @@
+//! Custom forces for Rust users of the physics core. Plugins are not available from wasm.
+
+use crate::physics::PhysicsObject;
+use crate::types::{Field, MathSpace};
+
+/// Extra acceleration added to every element after gravity, whenever `PhysicsSpace`
+/// evaluates the force law. It must only depend on its arguments, so runs stay repeatable.
+pub trait ForcePlugin<K: Field> {
+    /// Adds the acceleration on `particle`, the element at `index`, to `out`. `particle` may
+    /// already be drifted while `neighbors` still holds the positions of the last tick.
+    fn accumulate(
+        &self,
+        index: usize,
+        particle: &PhysicsObject<K>,
+        neighbors: &dyn NeighborQuery<K>,
+        out: &mut [K; 2],
+    );
+}
+
+/// Acceleration that may change with the simulation time, like a moving attractor, a trap or
+/// uniform gravity. `PhysicsSpace` evaluates it at the time the position belongs to: the
+/// current tick count for the elements as they are, one more for drifted positions. The
+/// kicks of the kick-drift-kick integrator then stay symmetric in time, so a time-dependent
+/// force does not break the leapfrog.
+pub trait ExternalForce<K: Field> {
+    fn acceleration(&self, position: &[K; 2], time: f64) -> [K; 2];
+}
+
+/// Read access to the elements of the last tick.
+pub trait NeighborQuery<K: Field> {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn element(&self, index: usize) -> &PhysicsObject<K>;
+
+    /// Indices of the elements within `radius` of `point`, in index order.
+    fn within(&self, point: &[K; 2], radius: &K) -> Vec<usize>;
+
+    /// Indices of the `k` elements nearest to `point`, nearest first.
+    fn nearest(&self, point: &[K; 2], k: usize) -> Vec<usize>;
+}
+
+/// Answers queries by checking every element.
+pub(crate) struct DirectNeighbors<'a, K: Field, S: MathSpace<K>> {
+    pub elements: &'a [PhysicsObject<K>],
+    pub math_space: &'a S,
+}
+
+impl<'a, K: Field + PartialOrd, S: MathSpace<K>> NeighborQuery<K> for DirectNeighbors<'a, K, S> {
+    fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn element(&self, index: usize) -> &PhysicsObject<K> {
+        &self.elements[index]
+    }
+
+    fn within(&self, point: &[K; 2], radius: &K) -> Vec<usize> {
+        let m = self.math_space;
+        (0..self.elements.len())
+            .filter(|&i| m.distance(&self.elements[i].position_vector, point) <= *radius)
+            .collect()
+    }
+
+    fn nearest(&self, point: &[K; 2], k: usize) -> Vec<usize> {
+        let m = self.math_space;
+        let mut by_distance: Vec<(K, usize)> = self
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (m.distance(&e.position_vector, point), i))
+            .collect();
+        by_distance.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        by_distance.into_iter().take(k).map(|(_, i)| i).collect()
+    }
+}
+
+/// Sideways wind: accelerates every element along x by `strength` times its y coordinate.
+#[derive(Debug, Clone)]
+pub struct Wind<K: Field> {
+    pub strength: K,
+}
+
+impl<K: Field> ForcePlugin<K> for Wind<K> {
+    fn accumulate(
+        &self,
+        _index: usize,
+        particle: &PhysicsObject<K>,
+        _neighbors: &dyn NeighborQuery<K>,
+        out: &mut [K; 2],
+    ) {
+        out[0] = out[0].clone() + self.strength.clone() * particle.position_vector[1].clone();
+    }
+}
+
+/// Drag towards the local circular velocity around `center`, like a gas disk that
+/// circularizes orbits. The circular speed comes from the inward part of the acceleration
+/// accumulated before this plugin runs, so it should be added last. Only acts between
+/// `r_min` and `r_max` from `center`. Energy is not conserved.
+#[derive(Debug, Clone)]
+pub struct GasDrag {
+    pub strength: f64,
+    pub center: [f64; 2],
+    pub r_min: f64,
+    pub r_max: f64,
+}
+
+impl ForcePlugin<f64> for GasDrag {
+    fn accumulate(
+        &self,
+        _index: usize,
+        particle: &PhysicsObject<f64>,
+        _neighbors: &dyn NeighborQuery<f64>,
+        out: &mut [f64; 2],
+    ) {
+        let p = &particle.position_vector;
+        let offset = [p[0] - self.center[0], p[1] - self.center[1]];
+        let r = offset[0].hypot(offset[1]);
+        if r == 0.0 || r < self.r_min || r > self.r_max {
+            return;
+        }
+        let radial = [offset[0] / r, offset[1] / r];
+        let pull = -(out[0] * radial[0] + out[1] * radial[1]);
+        let v = &particle.direction_vector;
+        // Circulate in the particle's own sense, counterclockwise if it has none
+        let sense = if offset[0] * v[1] - offset[1] * v[0] < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let speed = (pull.max(0.0) * r).sqrt() * sense;
+        let circular = [-radial[1] * speed, radial[0] * speed];
+        out[0] -= self.strength * (v[0] - circular[0]);
+        out[1] -= self.strength * (v[1] - circular[1]);
+    }
+}
+
+/// Softened point mass following the pointer. It moves linearly from `from` to `to` over the
+/// tick starting at time `start`, so a fast pointer pulls along its path instead of jumping.
+#[derive(Debug, Clone)]
+pub struct PointerAttractor {
+    /// Gravitational constant times the mass of the pointer
+    pub strength: f64,
+    pub softening: f64,
+    pub from: [f64; 2],
+    pub to: [f64; 2],
+    pub start: f64,
+}
+
+impl PointerAttractor {
+    /// Position of the pointer at simulation time `time`.
+    pub fn position(&self, time: f64) -> [f64; 2] {
+        let t = (time - self.start).clamp(0.0, 1.0);
+        [
+            self.from[0] + (self.to[0] - self.from[0]) * t,
+            self.from[1] + (self.to[1] - self.from[1]) * t,
+        ]
+    }
+}
+
+impl ExternalForce<f64> for PointerAttractor {
+    fn acceleration(&self, position: &[f64; 2], time: f64) -> [f64; 2] {
+        let pointer = self.position(time);
+        let offset = [pointer[0] - position[0], pointer[1] - position[1]];
+        let squared = offset[0] * offset[0] + offset[1] * offset[1] + self.softening.powi(2);
+        if squared == 0.0 {
+            return [0.0, 0.0];
+        }
+        let scale = self.strength / (squared * squared.sqrt());
+        [scale * offset[0], scale * offset[1]]
+    }
+}
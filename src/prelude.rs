@@ -0,0 +1,25 @@
+//! Everything needed to run a simulation from Rust, without the wasm `Universe` facade.
+//!
+//! ```
+//! use wasm_generic_space::prelude::*;
+//!
+//! let bodies = vec![
+//!     PhysicsObject::new([0.0, 0.0], [0.0, 0.0], 10.0),
+//!     PhysicsObject::new([20.0, 0.0], [0.0, 0.7], 0.1),
+//!     PhysicsObject::new([-30.0, 0.0], [0.0, -0.6], 0.1),
+//! ];
+//! let space = EuclideanSpace {
+//!     field: std::marker::PhantomData,
+//! };
+//! let mut phys = PhysicsSpace::new(bodies, 1.0, space, 1000.0, 0.5);
+//! for _ in 0..100 {
+//!     phys.tick();
+//! }
+//! assert_eq!(phys.elements.len(), 3);
+//! assert_eq!(phys.tick_count(), 100);
+//! ```
+
+pub use crate::bounds::Bounds;
+pub use crate::builder::UniverseBuilder;
+pub use crate::physics::{PhysicsObject, PhysicsSpace};
+pub use crate::types::{EuclideanSpace, Field, MathSpace};
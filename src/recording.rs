@@ -0,0 +1,127 @@
+//! Recording exports of `Universe`: energy history, escape directions and group paths, kept
+//! up to date by every tick. Compiled with the `recording` feature.
+
+use crate::Universe;
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+/// What is recorded after each tick.
+#[derive(Clone)]
+pub(crate) struct Recording {
+    pub escape_histogram: Vec<f64>, //Escaped mass per direction, empty if disabled
+    pub recording_history: bool,
+    pub history: VecDeque<[f64; 3]>, //Simulation time, particle count and total energy per tick
+    pub history_length: usize,
+    /// Origin, interval, capacity and recorded path of each tracked group
+    pub group_tracks: Vec<(u16, u32, usize, VecDeque<[f64; 2]>)>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            escape_histogram: Vec::new(),
+            recording_history: false,
+            history: VecDeque::new(),
+            history_length: 300,
+            group_tracks: Vec::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Universe {
+    /// Records the center of mass of the particles with origin code `origin` every
+    /// `every_n_ticks` ticks, keeping the last `capacity` points for `group_track`. Replaces
+    /// an earlier track of the same group; zero for either stops tracking it.
+    pub fn track_group(&mut self, origin: u16, every_n_ticks: u32, capacity: usize) {
+        let tracks = &mut self.recording.group_tracks;
+        tracks.retain(|(group, ..)| *group != origin);
+        if every_n_ticks > 0 && capacity > 0 {
+            tracks.push((origin, every_n_ticks, capacity, VecDeque::new()));
+        }
+    }
+
+    /// Recorded centers of mass of group `origin`, oldest first, as `[x0, y0, x1, y1, ...]`.
+    /// Ticks in which the group had no mass are skipped. Empty if it is not tracked.
+    pub fn group_track(&self, origin: u16) -> Vec<f64> {
+        self.recording.group_tracks
+            .iter()
+            .find(|(group, ..)| *group == origin)
+            .map_or(Vec::new(), |(.., path)| path.iter().flatten().cloned().collect())
+    }
+
+    /// Starts recording the mass removed by cull rules per direction, in `bins` equal
+    /// angular bins counterclockwise from the positive x axis. The direction is taken
+    /// from the center of mass at the moment of removal. Zero bins disables the histogram.
+    pub fn enable_escape_histogram(&mut self, bins: u32) {
+        self.recording.escape_histogram = vec![0.0; bins as usize];
+    }
+
+    /// Escaped mass per angular bin, empty while disabled.
+    pub fn escape_histogram(&self) -> Vec<f64> {
+        self.recording.escape_histogram.clone()
+    }
+
+    pub fn reset_escape_histogram(&mut self) {
+        self.recording.escape_histogram.iter_mut().for_each(|m| *m = 0.0);
+    }
+
+    /// Records simulation time, particle count and total energy after every tick while
+    /// enabled, keeping the latest `set_history_length` entries. Computing the energy is
+    /// O(n²) per tick.
+    pub fn record_history(&mut self, enabled: bool) {
+        self.recording.recording_history = enabled;
+    }
+
+    /// Number of ticks kept by `record_history`, 300 by default. Older entries are dropped.
+    pub fn set_history_length(&mut self, length: u32) {
+        let recording = &mut self.recording;
+        recording.history_length = length.max(1) as usize;
+        while recording.history.len() > recording.history_length {
+            recording.history.pop_front();
+        }
+    }
+
+    /// Recorded ticks, oldest first, flattened as `[time, particle_count, total_energy, ...]`.
+    pub fn history(&self) -> Vec<f64> {
+        self.recording.history.iter().flatten().cloned().collect()
+    }
+}
+
+impl Universe {
+    /// Updates the escape histogram, the history and the group tracks after a tick.
+    pub(crate) fn record_tick(&mut self) {
+        let recording = &mut self.recording;
+        let bins = recording.escape_histogram.len();
+        for (offset, mass) in self.phys.escapes().iter().filter(|_| bins > 0) {
+            let angle = offset[1].atan2(offset[0]).rem_euclid(2.0 * std::f64::consts::PI);
+            let bin = (angle / (2.0 * std::f64::consts::PI) * bins as f64) as usize;
+            recording.escape_histogram[bin.min(bins - 1)] += mass;
+        }
+        if recording.recording_history {
+            if recording.history.len() == recording.history_length {
+                recording.history.pop_front();
+            }
+            recording.history.push_back([
+                self.phys.report().simulation_time,
+                self.phys.elements.len() as f64,
+                self.phys.total_energy(),
+            ]);
+        }
+        let tick_count = self.phys.tick_count();
+        for (origin, every, capacity, path) in recording.group_tracks.iter_mut() {
+            // `every` is never zero, see `track_group`
+            #[allow(clippy::manual_is_multiple_of)]
+            if tick_count % u64::from(*every) != 0 {
+                continue;
+            }
+            let stats = self.phys.group_stats(*origin);
+            if stats[1] > 0.0 {
+                if path.len() == *capacity {
+                    path.pop_front();
+                }
+                path.push_back([stats[2], stats[3]]);
+            }
+        }
+    }
+}
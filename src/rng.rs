@@ -0,0 +1,61 @@
+use rand::{Error, RngCore, SeedableRng};
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+/// PCG32 generator shared by every random operation of a universe.
+///
+/// Its whole state is a single `u64`, so it can be saved with `state` and restored with
+/// `from_state` to replay a run exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn from_state(state: u64) -> Self {
+        SimRng { state }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = u64::from(self.next_u32());
+        (u64::from(self.next_u32()) << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for SimRng {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: [u8; 8]) -> Self {
+        let mut rng = SimRng {
+            state: u64::from_le_bytes(seed).wrapping_add(INCREMENT),
+        };
+        rng.next_u32();
+        rng
+    }
+}
@@ -0,0 +1,134 @@
+//! Fast-forwards of `Universe` that run in slices and yield to the event loop between them,
+//! so long runs do not freeze the page. Compiled with the `scheduling` feature.
+
+use crate::Universe;
+use futures::future::{self, Loop};
+use futures::Future;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32);
+}
+
+/// State shared between a universe and its running `tick_async`.
+#[derive(Default)]
+struct AsyncRun {
+    cancelled: Cell<bool>,
+    finished: Cell<bool>,
+    dropped: Cell<bool>,
+}
+
+/// The universe's handle on its async run. Clones start without a run, and dropping the
+/// universe tells a running one to stop before it touches the freed universe.
+#[derive(Default)]
+pub(crate) struct AsyncSlot(Option<Rc<AsyncRun>>);
+
+impl AsyncSlot {
+    pub(crate) fn active(&self) -> bool {
+        match &self.0 {
+            Some(run) => !run.finished.get(),
+            None => false,
+        }
+    }
+}
+
+impl Clone for AsyncSlot {
+    fn clone(&self) -> AsyncSlot {
+        AsyncSlot::default()
+    }
+}
+
+impl Drop for AsyncSlot {
+    fn drop(&mut self) {
+        if let Some(run) = &self.0 {
+            run.dropped.set(true);
+        }
+    }
+}
+
+/// A promise resolved by `setTimeout(0)`, so awaiting it lets the browser handle events and
+/// paint before the next slice.
+fn next_task() -> js_sys::Promise {
+    js_sys::Promise::new(&mut |resolve, _| set_timeout(&resolve, 0))
+}
+
+fn failure(message: &str) -> Box<dyn Future<Item = Loop<f64, u32>, Error = JsValue>> {
+    Box::new(future::err(JsValue::from_str(message)))
+}
+
+#[wasm_bindgen]
+impl Universe {
+    /// Runs `total_steps` ticks, `steps_per_slice` at a time, and yields to the event loop
+    /// with `setTimeout(0)` after each slice. The promise resolves with the simulation time
+    /// that passed, also when `cancel_async` stops the run early. It rejects if another run
+    /// is active, if `steps_per_slice` is zero, if a cull callback is set, which slices do not
+    /// call, or if the universe is freed during the run.
+    ///
+    /// The run keeps ticking this universe in place, which is safe for universes owned by
+    /// JS. `UniverseSet::add` refuses a universe with a running run, and Rust code must not
+    /// move the universe either until the promise settles.
+    pub fn tick_async(&mut self, total_steps: u32, steps_per_slice: u32) -> js_sys::Promise {
+        let refuse = |message: &str| js_sys::Promise::reject(&JsValue::from_str(message));
+        if self.async_run.active() {
+            return refuse("another tick_async run is still active");
+        }
+        if steps_per_slice == 0 {
+            return refuse("steps_per_slice must be at least 1");
+        }
+        if self.cull_callback.is_some() {
+            return refuse("tick_async does not run cull callbacks, clear it first");
+        }
+        let run = Rc::new(AsyncRun::default());
+        self.async_run = AsyncSlot(Some(run.clone()));
+        let start = self.simulation_time();
+        let universe: *mut Universe = self;
+
+        let slices = future::loop_fn(total_steps, {
+            let run = run.clone();
+            move |remaining| -> Box<dyn Future<Item = Loop<f64, u32>, Error = JsValue>> {
+                if run.dropped.get() {
+                    return failure("the universe was freed during tick_async");
+                }
+                // Safety: the universe is alive, as its slot has not been dropped, and stays
+                // at this address, as JS owns it. Slices run from the event loop and call no
+                // JS code that could reach the universe, so no other reference is in use.
+                let universe = unsafe { &mut *universe };
+                if universe.cull_callback.is_some() {
+                    return failure("tick_async does not run cull callbacks");
+                }
+                let steps = if run.cancelled.get() { 0 } else { remaining.min(steps_per_slice) };
+                for _ in 0..steps {
+                    universe.step();
+                }
+                if steps == remaining || run.cancelled.get() {
+                    let elapsed = universe.simulation_time() - start;
+                    return Box::new(future::ok(Loop::Break(elapsed)));
+                }
+                Box::new(
+                    JsFuture::from(next_task()).map(move |_| Loop::Continue(remaining - steps)),
+                )
+            }
+        });
+        future_to_promise(slices.then(move |result| {
+            run.finished.set(true);
+            result.map(JsValue::from)
+        }))
+    }
+
+    /// Stops the running `tick_async` at the end of its current slice. Returns false if no
+    /// run is active.
+    pub fn cancel_async(&mut self) -> bool {
+        match &self.async_run.0 {
+            Some(run) if self.async_run.active() => {
+                run.cancelled.set(true);
+                true
+            }
+            _ => false,
+        }
+    }
+}
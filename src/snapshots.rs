@@ -0,0 +1,40 @@
+//! Snapshot exports of `Universe`: packed initial conditions and transferable frames.
+//! Compiled with the `snapshots` feature.
+
+use crate::{blob, frame, Universe};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl Universe {
+    /// Replaces all particles by those of a packed buffer, see the `blob` module for the
+    /// layout. Returns the number of particles, or an error for a malformed buffer, in which
+    /// case nothing changes.
+    pub fn load_blob(&mut self, data: &[u8]) -> Result<u32, JsValue> {
+        let elements = blob::decode(data).ok_or_else(|| {
+            JsValue::from_str("blob must be a u32 count followed by that many records")
+        })?;
+        self.phys.elements.clear();
+        for element in elements {
+            self.phys.spawn(element);
+        }
+        Ok(self.particle_count())
+    }
+
+    /// Packs the current particles into a new buffer that can be transferred with
+    /// `postMessage`. The layout is documented in the `frame` module.
+    pub fn export_frame(&self) -> js_sys::ArrayBuffer {
+        let bytes = frame::encode(
+            self.phys.tick_count() as u32,
+            self.export_channels,
+            &self.phys.elements,
+        );
+        js_sys::Uint8Array::from(&bytes[..]).buffer()
+    }
+
+    /// Positions `[x0, y0, x1, y1, ...]` from a buffer made by `export_frame`, without
+    /// needing a universe on the receiving side.
+    pub fn parse_frame_positions(buffer: &js_sys::ArrayBuffer) -> Result<Vec<f64>, JsValue> {
+        frame::decode_positions(&js_sys::Uint8Array::new(buffer).to_vec())
+            .ok_or_else(|| JsValue::from_str("not a version 1 particle frame"))
+    }
+}
@@ -12,11 +12,22 @@ pub trait Field:
     + std::fmt::Debug
     + Clone
 {
+    /// False for values like NaN or infinity. Fields without such values keep the default.
+    fn is_finite(&self) -> bool {
+        true
+    }
 }
 
 pub trait MathSpace<K: Field> {
     fn distance(&self, first: &[K; 2], second: &[K; 2]) -> K;
 
+    /// Square of `distance`. It needs no root, so comparisons against a squared threshold
+    /// work in fields without fractional powers, and skip the root in the others.
+    fn distance_squared(&self, first: &[K; 2], second: &[K; 2]) -> K {
+        let diff = self.sub(second, first);
+        diff[0].clone() * diff[0].clone() + diff[1].clone() * diff[1].clone()
+    }
+
     //fn scalar_product(first: [K;2], first: [K;2]) -> K;
 
     //fn dimension() -> u8;
@@ -28,16 +39,21 @@ pub trait MathSpace<K: Field> {
     fn mul(&self, scalar: &K, vector: &[K; 2]) -> [K; 2];
 }
 
-pub struct EuclideanSpace<K: Field + Pow<f32, Output = K>> {
+#[derive(Clone)]
+pub struct EuclideanSpace<K: Field> {
     pub field: std::marker::PhantomData<K>,
 }
 
 impl<K: Field + Pow<f32, Output = K>> MathSpace<K> for EuclideanSpace<K> {
     fn distance(&self, first: &[K; 2], second: &[K; 2]) -> K {
-        let diff = self.sub(&second, &first);
+        let diff = self.sub(second, first);
         self.scalar_product(diff.clone(), diff.clone()).pow(0.5f32)
     }
 
+    fn distance_squared(&self, first: &[K; 2], second: &[K; 2]) -> K {
+        EuclideanSpace::distance_squared(self, first, second)
+    }
+
     fn add(&self, first: &[K; 2], second: &[K; 2]) -> [K; 2] {
         [
             (first[0].clone() + second[0].clone()),
@@ -60,7 +76,17 @@ impl<K: Field + Pow<f32, Output = K>> MathSpace<K> for EuclideanSpace<K> {
     }
 }
 
-impl<K: Field + Pow<f32, Output = K>> EuclideanSpace<K> {
+impl<K: Field> EuclideanSpace<K> {
+    /// Squared distance, also available for fields without `Pow<f32>`, which cannot use
+    /// this space as a `MathSpace`.
+    pub fn distance_squared(&self, first: &[K; 2], second: &[K; 2]) -> K {
+        let diff = [
+            first[0].clone() - second[0].clone(),
+            first[1].clone() - second[1].clone(),
+        ];
+        self.scalar_product(diff.clone(), diff)
+    }
+
     fn scalar_product(&self, first: [K; 2], second: [K; 2]) -> K {
         (first[0].clone() * second[0].clone()) + (first[1].clone() * second[1].clone())
     }
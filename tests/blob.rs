@@ -0,0 +1,39 @@
+//! Native tests for packed initial conditions.
+
+#![cfg(feature = "snapshots")]
+
+extern crate wasm_generic_space;
+use wasm_generic_space::blob;
+use wasm_generic_space::Universe;
+
+fn pack(records: &[[f32; 5]]) -> Vec<u8> {
+    let mut bytes = (records.len() as u32).to_le_bytes().to_vec();
+    for value in records.iter().flat_map(|r| r.iter()) {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn blob_round_trips_into_a_universe() {
+    let records = [[1.5, -2.0, 0.25, 0.5, 3.0], [100.0, 7.0, -1.0, 0.0, 0.5]];
+    let mut u = Universe::empty(1.0, 1e6, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    assert_eq!(u.load_blob(&pack(&records)).unwrap(), 2);
+    assert_eq!(u.get_positions(), vec![1.5, -2.0, 100.0, 7.0]);
+    assert_eq!(u.get_velocities(), vec![0.25, 0.5, -1.0, 0.0]);
+    assert_eq!(u.get_masses(), vec![3.0, 0.5]);
+    assert_eq!(u.get_ids(), vec![1, 2]);
+}
+
+#[test]
+fn malformed_blobs_are_rejected() {
+    let good = pack(&[[1.0, 2.0, 3.0, 4.0, 5.0]]);
+    assert_eq!(blob::decode(&good).unwrap().len(), 1);
+    assert!(blob::decode(&good[..good.len() - 1]).is_none());
+    assert!(blob::decode(&[good.clone(), vec![0]].concat()).is_none());
+    assert!(blob::decode(&[1, 0]).is_none());
+    assert!(blob::decode(&pack(&[[1.0, 2.0, 3.0, 4.0, -5.0]])).is_none());
+    assert!(blob::decode(&pack(&[[f32::NAN, 2.0, 3.0, 4.0, 5.0]])).is_none());
+    assert_eq!(blob::decode(&pack(&[])).unwrap().len(), 0);
+}
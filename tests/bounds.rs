@@ -0,0 +1,69 @@
+//! Native tests for the rectangle geometry helpers.
+
+extern crate wasm_generic_space;
+use wasm_generic_space::bounds::Bounds;
+
+#[test]
+fn contains_is_half_open() {
+    let b = Bounds::new(0.0, 0.0, 10.0, 5.0);
+    assert!(b.contains(0.0, 0.0));
+    assert!(b.contains(9.99, 4.99));
+    assert!(!b.contains(10.0, 2.0));
+    assert!(!b.contains(2.0, 5.0));
+    assert!(!b.contains(-0.01, 2.0));
+    assert!(!Bounds::new(1.0, 1.0, 0.0, 0.0).contains(1.0, 1.0));
+}
+
+#[test]
+fn intersects_overlap_touching_and_containment() {
+    let b = Bounds::new(0.0, 0.0, 10.0, 10.0);
+    assert!(b.intersects(&Bounds::new(5.0, 5.0, 10.0, 10.0)));
+    assert!(b.intersects(&Bounds::new(10.0, 0.0, 5.0, 5.0)));
+    assert!(b.intersects(&Bounds::new(10.0, 10.0, 1.0, 1.0)));
+    assert!(b.intersects(&Bounds::new(2.0, 2.0, 1.0, 1.0)));
+    assert!(Bounds::new(2.0, 2.0, 1.0, 1.0).intersects(&b));
+    assert!(!b.intersects(&Bounds::new(10.01, 0.0, 5.0, 5.0)));
+    assert!(!b.intersects(&Bounds::new(0.0, -3.0, 5.0, 2.0)));
+}
+
+#[test]
+fn intersects_degenerate_boxes() {
+    let b = Bounds::new(0.0, 0.0, 10.0, 10.0);
+    let point = Bounds::new(3.0, 4.0, 0.0, 0.0);
+    assert!(b.intersects(&point));
+    assert!(point.intersects(&point));
+    assert!(!point.intersects(&Bounds::new(3.0, 4.5, 0.0, 0.0)));
+    assert!(b.intersects(&Bounds::new(10.0, 10.0, 0.0, 0.0)));
+}
+
+#[test]
+fn distance_squared_to_point() {
+    let b = Bounds::new(0.0, 0.0, 10.0, 10.0);
+    assert_eq!(b.distance_squared_to_point(5.0, 5.0), 0.0);
+    assert_eq!(b.distance_squared_to_point(10.0, 10.0), 0.0);
+    assert_eq!(b.distance_squared_to_point(13.0, 5.0), 9.0);
+    assert_eq!(b.distance_squared_to_point(5.0, -2.0), 4.0);
+    assert_eq!(b.distance_squared_to_point(13.0, 14.0), 25.0);
+    assert_eq!(
+        Bounds::new(1.0, 1.0, 0.0, 0.0).distance_squared_to_point(4.0, 5.0),
+        25.0
+    );
+}
+
+#[test]
+fn expand_union_and_area() {
+    let b = Bounds::new(0.0, 0.0, 10.0, 4.0);
+    assert_eq!(b.expand(1.0), Bounds::new(-1.0, -1.0, 12.0, 6.0));
+    assert_eq!(b.expand(0.0), b);
+    assert_eq!(b.area(), 40.0);
+    assert_eq!(Bounds::new(1.0, 1.0, 0.0, 0.0).area(), 0.0);
+
+    let other = Bounds::new(5.0, -2.0, 10.0, 3.0);
+    assert_eq!(b.union(&other), Bounds::new(0.0, -2.0, 15.0, 6.0));
+    assert_eq!(b.union(&other), other.union(&b));
+    assert_eq!(b.union(&Bounds::new(2.0, 2.0, 1.0, 1.0)), b);
+    assert_eq!(
+        Bounds::new(1.0, 1.0, 0.0, 0.0).union(&Bounds::new(3.0, 2.0, 0.0, 0.0)),
+        Bounds::new(1.0, 1.0, 2.0, 1.0)
+    );
+}
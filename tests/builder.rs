@@ -0,0 +1,83 @@
+//! Native tests for scene construction.
+
+extern crate wasm_generic_space;
+use wasm_generic_space::builder::UniverseBuilder;
+use wasm_generic_space::Universe;
+
+fn legacy_scene() -> UniverseBuilder {
+    UniverseBuilder::new()
+        .add_box(1000, [250.0, 250.0], [1200.0, 1200.0], 2.0, 0.0001)
+        .add_particle(800.0, 500.0, 0.0, 0.0, 1.0)
+}
+
+#[test]
+fn builder_matches_legacy_constructor() {
+    let legacy = Universe::new();
+    let built = legacy_scene().build();
+
+    assert_eq!(legacy.particle_count() as usize, built.elements.len());
+    let masses = legacy.get_masses();
+    assert_eq!(masses[1000], 1.0);
+    assert_eq!(built.elements[1000].mass, 1.0);
+    assert!(masses[..1000].iter().all(|&m| m == 0.0001));
+    assert!(built.elements[..1000].iter().all(|e| {
+        let p = e.position_vector;
+        let v = e.direction_vector;
+        e.mass == 0.0001
+            && (250.0..1200.0).contains(&p[0])
+            && (250.0..1200.0).contains(&p[1])
+            && v.iter().all(|c| c.abs() <= 2.0)
+    }));
+}
+
+#[test]
+fn seeded_builder_is_reproducible() {
+    let scene = UniverseBuilder::new()
+        .seed(7)
+        .add_disk(100, [0.0, 0.0], 50.0, 0.01, 1.0, 0.0)
+        .add_plummer(100, [500.0, 0.0], 20.0, 0.01);
+    let first = scene.build();
+    let second = scene.build();
+    assert_eq!(first.elements.len(), 201);
+    for (a, b) in first.elements.iter().zip(second.elements.iter()) {
+        assert_eq!(a.position_vector, b.position_vector);
+        assert_eq!(a.direction_vector, b.direction_vector);
+    }
+    let other = scene.clone().seed(8).build();
+    assert!(other.elements[5].position_vector != first.elements[5].position_vector);
+}
+
+#[test]
+fn disk_velocity_dispersion_adds_random_motion() {
+    let (g, mass, radius) = (100.0, 0.001, 200.0);
+    let disk = UniverseBuilder::new()
+        .seed(3)
+        .gravitational_constant(g)
+        .add_disk(4000, [0.0, 0.0], radius, mass, 1.0, 0.5)
+        .build();
+    let disk_mass = 4000.0 * mass;
+    let mut sum = 0.0;
+    for e in disk.elements[1..].iter() {
+        let [x, y] = e.position_vector;
+        let r = x.hypot(y);
+        let speed = (g * (1.0 + disk_mass * (r / radius).powi(2)) / r).sqrt();
+        let dx = e.direction_vector[0] + speed * y / r;
+        let dy = e.direction_vector[1] - speed * x / r;
+        sum += dx * dx + dy * dy;
+    }
+    let sigma = (sum / (2.0 * 4000.0)).sqrt();
+    assert!((sigma - 0.5).abs() < 0.02, "sigma {}", sigma);
+}
+
+#[test]
+fn cold_collapse_is_a_cold_disk_with_the_default_settings() {
+    let u = Universe::cold_collapse(200, 100.0, -50.0, 30.0, 0.5);
+    assert_eq!(u.particle_count(), 200);
+    assert_eq!(u.gravitational_constant(), 100.0);
+    assert!(u.get_masses().iter().all(|&m| m == 0.5));
+    assert!(u.get_velocities().iter().all(|&v| v == 0.0));
+    let positions = u.get_positions();
+    assert!(positions
+        .chunks(2)
+        .all(|p| (p[0] - 100.0).hypot(p[1] + 50.0) <= 30.0));
+}
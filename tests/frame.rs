@@ -0,0 +1,35 @@
+//! Native tests for the packed particle frames.
+
+extern crate wasm_generic_space;
+use wasm_generic_space::frame;
+use wasm_generic_space::prelude::*;
+
+#[test]
+fn frame_layout_and_positions_round_trip() {
+    let elements = vec![
+        PhysicsObject::new([1.5, -2.0], [0.25, 0.5], 3.0),
+        PhysicsObject::new([1e9, 7.0], [-1.0, 0.0], 0.5),
+    ];
+    let plain = frame::encode(42, 0, &elements);
+    assert_eq!(plain.len(), frame::HEADER_BYTES + 2 * 16);
+    assert_eq!(&plain[0..4], &frame::VERSION.to_le_bytes());
+    assert_eq!(&plain[8..12], &2u32.to_le_bytes());
+    assert_eq!(&plain[12..16], &42u32.to_le_bytes());
+    let positions = vec![1.5, -2.0, 1e9, 7.0];
+    assert_eq!(frame::decode_positions(&plain), Some(positions.clone()));
+
+    let full = frame::encode(42, frame::VELOCITIES | frame::MASSES | 8, &elements);
+    assert_eq!(&full[4..8], &3u32.to_le_bytes());
+    assert_eq!(full.len(), frame::HEADER_BYTES + 2 * 40);
+    assert_eq!(&full[48..56], &0.25f64.to_le_bytes());
+    assert_eq!(&full[80..88], &3.0f64.to_le_bytes());
+    assert_eq!(frame::decode_positions(&full), Some(positions));
+
+    assert_eq!(frame::decode_positions(&plain[..40]), None);
+    let mut future = plain.clone();
+    future[0] = 2;
+    assert_eq!(frame::decode_positions(&future), None);
+    let mut huge = plain.clone();
+    huge[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(frame::decode_positions(&huge), None);
+}
@@ -0,0 +1,579 @@
+//! Native tests for the generic physics core.
+
+extern crate num_traits;
+extern crate wasm_generic_space;
+
+use num_traits::{Inv, One, Pow, Zero};
+use rand::SeedableRng;
+use std::ops::{Add, Mul, Sub};
+use wasm_generic_space::physics::{
+    CullRule, Integrator, PhysicsObject, PhysicsSpace, SofteningKernel,
+};
+use wasm_generic_space::plugin::{ExternalForce, ForcePlugin, NeighborQuery, Wind};
+use wasm_generic_space::rng::SimRng;
+use wasm_generic_space::types::{EuclideanSpace, Field, MathSpace};
+
+/// Signed 32.32 fixed-point number.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+struct Fixed(i64);
+
+const FRAC_BITS: u32 = 32;
+
+impl Fixed {
+    fn from_f64(v: f64) -> Self {
+        Fixed((v * (1u64 << FRAC_BITS) as f64) as i64)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / (1u64 << FRAC_BITS) as f64
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((i128::from(self.0) * i128::from(other.0)) >> FRAC_BITS) as i64)
+    }
+}
+
+impl Zero for Fixed {
+    fn zero() -> Fixed {
+        Fixed(0)
+    }
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl One for Fixed {
+    fn one() -> Fixed {
+        Fixed(1 << FRAC_BITS)
+    }
+}
+
+impl Inv for Fixed {
+    type Output = Fixed;
+    fn inv(self) -> Fixed {
+        Fixed(((1i128 << (2 * FRAC_BITS)) / i128::from(self.0)) as i64)
+    }
+}
+
+impl Pow<f32> for Fixed {
+    type Output = Fixed;
+    fn pow(self, exponent: f32) -> Fixed {
+        Fixed::from_f64(self.to_f64().powf(f64::from(exponent)))
+    }
+}
+
+impl Field for Fixed {}
+
+fn fixed_space() -> PhysicsSpace<Fixed, EuclideanSpace<Fixed>> {
+    let f = Fixed::from_f64;
+    let elements = vec![
+        PhysicsObject::new([f(0.0), f(0.0)], [f(0.0), f(0.0)], f(10.0)),
+        PhysicsObject::new([f(20.0), f(0.0)], [f(0.0), f(0.5)], f(0.1)),
+        PhysicsObject::new([f(-30.0), f(5.0)], [f(0.0), f(-0.4)], f(0.1)),
+    ];
+    PhysicsSpace::new(
+        elements,
+        f(1.0),
+        EuclideanSpace {
+            field: std::marker::PhantomData,
+        },
+        f(1000.0),
+        f(0.5),
+    )
+}
+
+#[test]
+fn fixed_point_field_is_finite_and_deterministic() {
+    let mut first = fixed_space();
+    let mut second = fixed_space();
+    for _ in 0..100 {
+        first.tick();
+        second.tick();
+    }
+
+    assert_eq!(first.elements.len(), 3);
+    for (a, b) in first.elements.iter().zip(second.elements.iter()) {
+        assert_eq!(a.position_vector, b.position_vector);
+        assert_eq!(a.direction_vector, b.direction_vector);
+        assert!(a.position_vector.iter().all(|p| p.to_f64().is_finite()));
+    }
+    // The light bodies are pulled towards the heavy one
+    let mut pulled = fixed_space();
+    let before = pulled.elements.clone();
+    pulled.tick();
+    for i in 1..3 {
+        let (p, heavy) = (before[i].position_vector, before[0].position_vector);
+        let offset = [(p[0] - heavy[0]).to_f64(), (p[1] - heavy[1]).to_f64()];
+        let (v, v0) = (pulled.elements[i].direction_vector, before[i].direction_vector);
+        let dv = [(v[0] - v0[0]).to_f64(), (v[1] - v0[1]).to_f64()];
+        assert!(dv[0] * offset[0] + dv[1] * offset[1] < 0.0, "{:?} {:?}", dv, offset);
+    }
+}
+
+/// Exact fraction, which has no fractional powers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fraction(i64, i64);
+
+impl Fraction {
+    fn new(numerator: i64, denominator: i64) -> Self {
+        let (mut a, mut b) = (numerator.abs(), denominator.abs());
+        while b != 0 {
+            let r = a % b;
+            a = b;
+            b = r;
+        }
+        let divisor = a.max(1) * denominator.signum();
+        Fraction(numerator / divisor, denominator / divisor)
+    }
+}
+
+impl Add for Fraction {
+    type Output = Fraction;
+    fn add(self, other: Fraction) -> Fraction {
+        Fraction::new(self.0 * other.1 + other.0 * self.1, self.1 * other.1)
+    }
+}
+
+impl Sub for Fraction {
+    type Output = Fraction;
+    fn sub(self, other: Fraction) -> Fraction {
+        Fraction::new(self.0 * other.1 - other.0 * self.1, self.1 * other.1)
+    }
+}
+
+impl Mul for Fraction {
+    type Output = Fraction;
+    fn mul(self, other: Fraction) -> Fraction {
+        Fraction::new(self.0 * other.0, self.1 * other.1)
+    }
+}
+
+impl Zero for Fraction {
+    fn zero() -> Fraction {
+        Fraction(0, 1)
+    }
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl One for Fraction {
+    fn one() -> Fraction {
+        Fraction(1, 1)
+    }
+}
+
+impl Inv for Fraction {
+    type Output = Fraction;
+    fn inv(self) -> Fraction {
+        Fraction::new(self.1, self.0)
+    }
+}
+
+impl Field for Fraction {}
+
+#[test]
+fn squared_distance_is_exact_without_fractional_powers() {
+    let space: EuclideanSpace<Fraction> = EuclideanSpace {
+        field: std::marker::PhantomData,
+    };
+    let origin = [Fraction::zero(), Fraction::zero()];
+    let point = [Fraction::new(1, 3), Fraction::new(-1, 4)];
+    assert_eq!(space.distance_squared(&origin, &point), Fraction::new(25, 144));
+    assert_eq!(space.distance_squared(&point, &origin), Fraction::new(25, 144));
+    assert_eq!(space.distance_squared(&point, &point), Fraction::zero());
+
+    // The trait method agrees with the square of the distance
+    let f64_space: EuclideanSpace<f64> = EuclideanSpace {
+        field: std::marker::PhantomData,
+    };
+    let (a, b) = ([1.0, 2.0], [4.0, -2.0]);
+    assert_eq!(MathSpace::distance_squared(&f64_space, &a, &b), 25.0);
+    assert_eq!(f64_space.distance(&a, &b), 5.0);
+}
+
+fn resting_space(ys: &[f64]) -> PhysicsSpace<f64, EuclideanSpace<f64>> {
+    let elements = ys
+        .iter()
+        .map(|&y| PhysicsObject::new([0.0, y], [0.0, 0.0], 1.0))
+        .collect();
+    PhysicsSpace::new(
+        elements,
+        0.0,
+        EuclideanSpace {
+            field: std::marker::PhantomData,
+        },
+        1000.0,
+        0.01,
+    )
+}
+
+#[test]
+fn wind_plugin_pushes_along_x_by_height() {
+    let mut plain = resting_space(&[-10.0, 0.0, 10.0]);
+    let mut windy = resting_space(&[-10.0, 0.0, 10.0]);
+    windy.add_plugin(Box::new(Wind { strength: 0.01 }));
+    for _ in 0..10 {
+        plain.tick();
+        windy.tick();
+    }
+
+    assert!(plain.elements.iter().all(|e| e.position_vector[0] == 0.0));
+    let x: Vec<f64> = windy.elements.iter().map(|e| e.position_vector[0]).collect();
+    assert!(x[0] < 0.0);
+    assert_eq!(x[1], 0.0);
+    assert!((x[2] + x[0]).abs() < 1e-12);
+    // The wind only acts along x
+    assert!(windy.elements.iter().all(|e| e.direction_vector[1] == 0.0));
+}
+
+/// Pushes every element away from its nearest neighbor, checking the arguments it gets.
+struct Repel;
+
+impl ForcePlugin<f64> for Repel {
+    fn accumulate(
+        &self,
+        index: usize,
+        particle: &PhysicsObject<f64>,
+        neighbors: &dyn NeighborQuery<f64>,
+        out: &mut [f64; 2],
+    ) {
+        assert_eq!(neighbors.element(index).id, particle.id);
+        let nearest = neighbors.nearest(&particle.position_vector, 2);
+        assert_eq!(nearest[0], index);
+        let other = &neighbors.element(nearest[1]).position_vector;
+        out[1] += 0.01 * (particle.position_vector[1] - other[1]).signum();
+        assert_eq!(neighbors.within(&particle.position_vector, &0.5), vec![index]);
+    }
+}
+
+#[test]
+fn plugins_see_their_element_and_its_neighbors() {
+    let mut space = resting_space(&[-1.0, 1.0, 5.0]);
+    space.add_plugin(Box::new(Repel));
+    space.tick();
+    let vy: Vec<f64> = space.elements.iter().map(|e| e.direction_vector[1]).collect();
+    assert!(vy[0] < 0.0);
+    assert!(vy[1] > 0.0);
+    assert!(vy[2] > 0.0);
+}
+
+#[test]
+fn plugins_are_replaced_and_removed_by_id() {
+    let mut space = resting_space(&[-10.0, 0.0, 10.0]);
+    let wind = space.add_plugin(Box::new(Wind { strength: 0.01 }));
+    let repel = space.add_plugin(Box::new(Repel));
+    assert!(space.replace_plugin(wind, Box::new(Wind { strength: -0.01 })));
+    assert!(space.remove_plugin(repel));
+    assert!(!space.remove_plugin(repel));
+    assert!(!space.replace_plugin(repel, Box::new(Repel)));
+    space.tick();
+    assert!(space.elements[0].direction_vector[0] > 0.0);
+    assert!(space.elements.iter().all(|e| e.direction_vector[1] == 0.0));
+}
+
+#[test]
+fn max_age_keeps_elements_born_in_the_future() {
+    let mut space = resting_space(&[0.0, 10.0]);
+    space.elements[0].birth_tick = 5;
+    space.add_cull_rule(CullRule::MaxAge(2));
+    space.tick();
+    assert_eq!(space.elements.len(), 2);
+    // The other element gets older than two ticks
+    for _ in 0..3 {
+        space.tick();
+    }
+    assert_eq!(space.elements.len(), 1);
+    assert_eq!(space.elements[0].birth_tick, 5);
+}
+
+#[test]
+fn scattering_conserves_each_pair() {
+    let elements = vec![
+        PhysicsObject::new([0.0, 0.0], [0.1, 0.0], 2.0),
+        PhysicsObject::new([1.0, 0.0], [-0.3, 0.2], 1.0),
+        PhysicsObject::new([10.0, 5.0], [0.0, 0.4], 0.5),
+        PhysicsObject::new([10.5, 5.0], [0.2, -0.1], 3.0),
+    ];
+    let mut space = PhysicsSpace::new(
+        elements,
+        1.0,
+        EuclideanSpace {
+            field: std::marker::PhantomData,
+        },
+        1000.0,
+        0.01,
+    );
+    let before = space.elements.clone();
+    space.scatter(1.0, &mut SimRng::seed_from_u64(3));
+
+    for pair in [(0, 1), (2, 3)].iter() {
+        let (i, j) = *pair;
+        let momentum = |e: &[PhysicsObject<f64>]| {
+            let p = |k: usize, axis: usize| e[k].mass * e[k].direction_vector[axis];
+            [p(i, 0) + p(j, 0), p(i, 1) + p(j, 1)]
+        };
+        let kinetic = |e: &[PhysicsObject<f64>]| {
+            let k = |k: usize| {
+                let v = e[k].direction_vector;
+                0.5 * e[k].mass * (v[0] * v[0] + v[1] * v[1])
+            };
+            k(i) + k(j)
+        };
+        let (p0, p1) = (momentum(&before), momentum(&space.elements));
+        assert!((p0[0] - p1[0]).abs() < 1e-12 && (p0[1] - p1[1]).abs() < 1e-12);
+        assert!((kinetic(&before) - kinetic(&space.elements)).abs() < 1e-12);
+        assert_ne!(before[i].direction_vector, space.elements[i].direction_vector);
+        assert_eq!(before[i].position_vector, space.elements[i].position_vector);
+    }
+}
+
+fn space_of(elements: Vec<PhysicsObject<f64>>) -> PhysicsSpace<f64, EuclideanSpace<f64>> {
+    PhysicsSpace::new(
+        elements,
+        1.0,
+        EuclideanSpace {
+            field: std::marker::PhantomData,
+        },
+        1e6,
+        0.01,
+    )
+}
+
+#[test]
+fn empty_and_single_element_spaces_tick() {
+    for &integrator in [Integrator::Hybrid, Integrator::KickDriftKick].iter() {
+        let mut empty = space_of(Vec::new());
+        empty.set_integrator(integrator);
+        empty.tick();
+        assert!(empty.elements.is_empty());
+        assert_eq!(empty.total_energy(), 0.0);
+        assert_eq!(empty.half_mass_radius(), 0.0);
+
+        let mut single = space_of(vec![PhysicsObject::new([1.0, 2.0], [0.5, -0.25], 3.0)]);
+        single.set_integrator(integrator);
+        for _ in 0..4 {
+            single.tick();
+        }
+        let e = &single.elements[0];
+        assert_eq!(e.position_vector, [3.0, 1.0]);
+        assert_eq!(e.direction_vector, [0.5, -0.25]);
+        assert_eq!(e.acceleration_vector, [0.0, 0.0]);
+    }
+}
+
+#[test]
+fn regularized_pair_feels_the_softened_force() {
+    // Circular orbit for the Plummer-softened pull of two unit masses 10 apart
+    let relative_acc = 2.0 * 10.0 / 200f64.powf(1.5);
+    let v = (relative_acc * 10.0).sqrt() / 2.0;
+    let mut space = space_of(vec![
+        PhysicsObject::new([-5.0, 0.0], [0.0, -v], 1.0),
+        PhysicsObject::new([5.0, 0.0], [0.0, v], 1.0),
+    ]);
+    space.set_softening(10.0);
+    space.set_binary_regularization(Some((16, 30.0)));
+    for _ in 0..300 {
+        space.tick();
+        let (a, b) = (&space.elements[0].position_vector, &space.elements[1].position_vector);
+        assert!(((a[0] - b[0]).hypot(a[1] - b[1]) - 10.0).abs() < 0.05);
+    }
+    assert_eq!(space.regularized_pairs(), 1);
+}
+
+#[test]
+fn field_follows_gravity_switch_and_self_gravity() {
+    let mut space = space_of(vec![
+        PhysicsObject::new([0.0, 0.0], [0.0, 0.0], 100.0),
+        PhysicsObject::new([20.0, 0.0], [0.0, 0.0], 1.0),
+    ]);
+    assert_eq!(space.field_at(&[10.0, 0.0]), [-1.0 + 0.01, 0.0]);
+    space.set_self_gravity(false);
+    space.set_heavy_threshold(10.0);
+    assert_eq!(space.field_at(&[10.0, 0.0]), [-1.0, 0.0]);
+    space.set_gravity_enabled(false);
+    assert_eq!(space.field_at(&[10.0, 0.0]), [0.0, 0.0]);
+}
+
+#[test]
+fn two_elements_feel_exactly_the_pair_force() {
+    let mut space = space_of(vec![
+        PhysicsObject::new([0.0, 0.0], [0.0, 0.1], 4.0),
+        PhysicsObject::new([30.0, 40.0], [0.0, -0.1], 1.0),
+    ]);
+    space.set_integrator(Integrator::KickDriftKick);
+    for _ in 0..3 {
+        space.tick();
+        for &(i, j) in [(0, 1), (1, 0)].iter() {
+            let force = space.pair_force(i, j);
+            let e = &space.elements[i];
+            for (a, f) in e.acceleration_vector.iter().zip(force.iter()) {
+                assert!((e.mass * a - f).abs() < 1e-15);
+            }
+        }
+        let (f01, f10) = (space.pair_force(0, 1), space.pair_force(1, 0));
+        assert_eq!([f01[0] + f10[0], f01[1] + f10[1]], [0.0, 0.0]);
+    }
+}
+
+#[test]
+fn cubic_spline_softening_is_newtonian_beyond_the_softening_length() {
+    let mut newtonian = space_of(vec![PhysicsObject::new([0.0, 0.0], [0.0, 0.0], 1.0)]);
+    let mut plummer = newtonian.clone();
+    plummer.set_softening(1.0);
+    let mut spline = plummer.clone();
+    spline.set_softening_kernel(SofteningKernel::CubicSpline);
+    for &d in [1.0, 1.5, 3.0, 20.0].iter() {
+        let exact = newtonian.field_at(&[d, 0.0]);
+        assert_eq!(spline.field_at(&[d, 0.0]), exact);
+        assert!(plummer.field_at(&[d, 0.0])[0] / exact[0] < 0.999);
+    }
+    for &d in [0.1, 0.5, 0.9].iter() {
+        assert!(spline.field_at(&[d, 0.0])[0] / newtonian.field_at(&[d, 0.0])[0] < 0.999);
+    }
+
+    // Forces are continuous and the potential is consistent with them on both kernels
+    for space in [&mut plummer, &mut spline].iter_mut() {
+        let edge = |d: f64| space.field_at(&[d, 0.0])[0];
+        assert!((edge(0.5 - 1e-9) - edge(0.5 + 1e-9)).abs() < 1e-6);
+        assert!((edge(1.0 - 1e-9) - edge(1.0 + 1e-9)).abs() < 1e-6);
+        space.elements.push(PhysicsObject::new([0.0, 0.0], [0.0, 0.0], 0.0));
+        for &d in [0.2, 0.5, 0.7, 1.0, 2.0].iter() {
+            let potential = |space: &mut PhysicsSpace<_, _>, d: f64| {
+                space.elements[1].position_vector = [d, 0.0];
+                space.potential(1)
+            };
+            let h = 1e-6;
+            let slope = (potential(space, d + h) - potential(space, d - h)) / (2.0 * h);
+            assert!((slope + space.field_at(&[d, 0.0])[0]).abs() < 1e-6, "{}", d);
+        }
+    }
+    newtonian.set_softening_kernel(SofteningKernel::CubicSpline);
+    assert_eq!(newtonian.field_at(&[0.5, 0.0]), [-4.0, 0.0]);
+}
+
+/// Harmonic trap whose center swings along x.
+struct MovingTrap {
+    stiffness: f64,
+    amplitude: f64,
+    frequency: f64,
+}
+
+impl MovingTrap {
+    fn at(&self, p: &[f64; 2], time: f64) -> [f64; 2] {
+        let center = self.amplitude * (self.frequency * time).sin();
+        [-self.stiffness * (p[0] - center), -self.stiffness * p[1]]
+    }
+}
+
+impl ExternalForce<f64> for MovingTrap {
+    fn acceleration(&self, position: &[f64; 2], time: f64) -> [f64; 2] {
+        self.at(position, time)
+    }
+}
+
+#[test]
+fn moving_trap_matches_fine_step_solution() {
+    let trap = || MovingTrap {
+        stiffness: 0.01,
+        amplitude: 10.0,
+        frequency: 0.05,
+    };
+    let mut space = space_of(vec![PhysicsObject::new([5.0, 0.0], [0.0, 0.3], 1.0)]);
+    space.set_integrator(Integrator::KickDriftKick);
+    space.add_external_force(Box::new(trap()));
+    // The first kick uses the stored acceleration, which predates the force
+    space.elements[0].acceleration_vector = trap().at(&[5.0, 0.0], 0.0);
+
+    // Reference: classic Runge-Kutta with a hundred steps per tick
+    let reference = trap();
+    let derivative = |s: [f64; 4], t: f64| {
+        let a = reference.at(&[s[0], s[1]], t);
+        [s[2], s[3], a[0], a[1]]
+    };
+    let (mut state, dt) = ([5.0, 0.0, 0.0, 0.3], 0.01);
+    for step in 0..30_000 {
+        let t = f64::from(step) * dt;
+        let add = |s: [f64; 4], d: [f64; 4], f: f64| {
+            [s[0] + f * d[0], s[1] + f * d[1], s[2] + f * d[2], s[3] + f * d[3]]
+        };
+        let k1 = derivative(state, t);
+        let k2 = derivative(add(state, k1, dt / 2.0), t + dt / 2.0);
+        let k3 = derivative(add(state, k2, dt / 2.0), t + dt / 2.0);
+        let k4 = derivative(add(state, k3, dt), t + dt);
+        for i in 0..4 {
+            state[i] += dt / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+        }
+        if step % 100 == 99 {
+            space.tick();
+            let p = space.elements[0].position_vector;
+            let error = (p[0] - state[0]).hypot(p[1] - state[1]);
+            assert!(error < 0.2, "{} after {} ticks", error, space.tick_count());
+        }
+    }
+}
+
+/// Harmonic trap whose center moves along x at a constant speed.
+struct SlidingTrap {
+    stiffness: f64,
+    speed: f64,
+}
+
+impl ExternalForce<f64> for SlidingTrap {
+    fn acceleration(&self, position: &[f64; 2], time: f64) -> [f64; 2] {
+        let center = self.speed * time;
+        [-self.stiffness * (position[0] - center), -self.stiffness * position[1]]
+    }
+}
+
+#[test]
+fn sliding_trap_is_evaluated_at_the_substep_times() {
+    let trap = || SlidingTrap {
+        stiffness: 0.01,
+        speed: 0.5,
+    };
+    let mut space = space_of(vec![PhysicsObject::new([0.0, 2.0], [0.0, 0.0], 1.0)]);
+    space.set_integrator(Integrator::KickDriftKick);
+    space.add_external_force(Box::new(trap()));
+    // Adding the force does not refresh the stored acceleration the first kick uses
+    space.compute_accelerations();
+
+    // Reference: leapfrog with a thousand steps per tick
+    let (reference, dt) = (trap(), 0.001);
+    let (mut p, mut v) = ([0.0, 2.0], [0.0, 0.0]);
+    let mut a = reference.acceleration(&p, 0.0);
+    for tick in 1..=300 {
+        for step in 0..1000 {
+            let t = f64::from(tick - 1) + f64::from(step + 1) * dt;
+            for i in 0..2 {
+                v[i] += a[i] * dt / 2.0;
+                p[i] += v[i] * dt;
+            }
+            a = reference.acceleration(&p, t);
+            for i in 0..2 {
+                v[i] += a[i] * dt / 2.0;
+            }
+        }
+        space.tick();
+        let q = space.elements[0].position_vector;
+        let error = (q[0] - p[0]).hypot(q[1] - p[1]);
+        assert!(error < 0.15, "{} after {} ticks", error, tick);
+    }
+}
@@ -11,3 +11,254 @@ wasm_bindgen_test_configure!(run_in_browser);
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn cull_callback_removes_returned_indices() {
+    let mut u = wasm_generic_space::Universe::empty(0.0, 1000.0, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    u.add_particle(10.0, 0.0, 0.0, 0.0, 2.0).unwrap();
+    u.add_particle(20.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    let f = js_sys::Function::new_with_args("data", "return data[9] > 1.5 ? [1] : [];");
+    u.set_cull_callback(&f);
+    u.tick().unwrap();
+    assert_eq!(u.particle_count(), 2);
+    assert_eq!(u.get_masses(), vec![1.0, 1.0]);
+}
+
+#[wasm_bindgen_test]
+fn cull_callback_keeps_protected_particles() {
+    let mut u = wasm_generic_space::Universe::empty(0.0, 1000.0, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    u.add_particle(10.0, 0.0, 0.0, 0.0, 2.0).unwrap();
+    assert!(u.set_protected(1, true));
+    let f = js_sys::Function::new_with_args("data", "return [0, 1];");
+    u.set_cull_callback(&f);
+    u.tick().unwrap();
+    assert_eq!(u.get_masses(), vec![2.0]);
+}
+
+#[wasm_bindgen_test]
+fn cull_callback_errors_leave_particles_alone() {
+    let mut u = wasm_generic_space::Universe::empty(0.0, 1000.0, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    u.add_particle(10.0, 0.0, 0.0, 0.0, 2.0).unwrap();
+    let bodies = ["return [-1];", "return [NaN];", "return [0.5];", "return [2];", "throw 1;"];
+    for body in bodies.iter() {
+        u.set_cull_callback(&js_sys::Function::new_with_args("data", body));
+        assert!(u.tick().is_err(), "{}", body);
+        assert_eq!(u.particle_count(), 2);
+    }
+}
+
+#[wasm_bindgen_test]
+fn benchmark_leaves_universe_untouched() {
+    let u = wasm_generic_space::Universe::new();
+    let before = u.get_positions();
+    let result = u.benchmark(5);
+    assert!(result.ticks_per_second() > 0.0);
+    assert!(result.particle_ticks_per_second() > 0.0);
+    assert!(result.tick_ms_avg() > 0.0);
+    assert_eq!(u.get_positions(), before);
+}
+
+#[wasm_bindgen_test]
+fn universe_from_builder_json() {
+    let u = wasm_generic_space::Universe::from_builder_json(
+        r#"{"seed": 3, "components": [
+            {"type": "disk", "count": 10, "center": [0, 0], "radius": 50,
+             "particle_mass": 0.1, "central_mass": 1},
+            {"type": "particle", "x": 100, "y": 0, "mass": 2}]}"#,
+    )
+    .unwrap();
+    assert_eq!(u.particle_count(), 12);
+    assert_eq!(u.get_masses()[11], 2.0);
+    let unknown = r#"{"components": [{"type": "x"}]}"#;
+    assert!(wasm_generic_space::Universe::from_builder_json(unknown).is_err());
+}
+
+#[wasm_bindgen_test]
+fn non_finite_input_is_rejected() {
+    let mut u = wasm_generic_space::Universe::empty(1.0, 1000.0, 0.01);
+    assert!(u.add_particle(0.0, f64::NAN, 0.0, 0.0, 1.0).is_err());
+    assert!(u.add_particle(0.0, 0.0, 0.0, 0.0, std::f64::INFINITY).is_err());
+    assert_eq!(u.particle_count(), 0);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    let handle = u.handle(0).unwrap();
+    assert!(handle.set_velocity(&mut u, f64::NAN, 0.0).is_err());
+    assert_eq!(handle.velocity(&u).unwrap(), vec![0.0, 0.0]);
+    assert!(u.set_pointer(f64::NAN, 0.0, 1.0).is_err());
+    assert!(u.set_pointer(0.0, 0.0, std::f64::INFINITY).is_err());
+    assert!(u.set_pointer_position_smoothing(f64::NAN).is_err());
+    assert_eq!(u.pointer_position(), Vec::<f64>::new());
+    let overflow = r#"{"components": [{"type": "particle", "x": 1e999, "y": 0, "mass": 1}]}"#;
+    assert!(wasm_generic_space::Universe::from_builder_json(overflow).is_err());
+}
+
+#[wasm_bindgen_test]
+fn non_finite_transforms_and_bounds_are_rejected() {
+    let mut u = wasm_generic_space::Universe::empty(1.0, 1000.0, 0.01);
+    u.add_particle(1.0, 2.0, 0.0, 0.0, 1.0).unwrap();
+    assert!(u.translate(f64::NAN, 0.0).is_err());
+    assert!(u.rotate(std::f64::INFINITY, 0.0, 0.0).is_err());
+    assert!(u.explode(0.0, 0.0, f64::NAN, 0.1).is_err());
+    assert!(u.split_particle(0, 2, f64::NAN).is_err());
+    assert_eq!(u.get_positions(), vec![1.0, 2.0]);
+    assert!(u.add_cull_rule("radius", f64::NAN).is_err());
+    assert!(u.add_cull_rule("max_age", -1.0).is_err());
+    assert!(u.add_cull_rule("max_age", 1.5).is_err());
+    assert!(u.set_cull_center(f64::NAN, 0.0).is_err());
+    assert!(u.set_rect_bounds(0.0, 0.0, std::f64::INFINITY, 1.0).is_err());
+    assert!(u.add_absorber_rect(0.0, 0.0, f64::NAN, 1.0).is_err());
+    assert!(u.add_absorber_circle(0.0, 0.0, f64::NAN).is_err());
+    u.tick().unwrap();
+    assert_eq!(u.particle_count(), 1);
+}
+
+#[wasm_bindgen_test]
+fn stale_particle_handles_report_their_id() {
+    let mut u = wasm_generic_space::Universe::empty(0.0, 1000.0, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    let handle = u.handle(0).unwrap();
+    assert!(u.remove_particle(0));
+    u.tick().unwrap();
+    let message = |error: wasm_bindgen::JsValue| error.as_string().unwrap();
+    let expected = format!("particle {} was culled or merged", handle.id());
+    assert_eq!(message(handle.position(&u).unwrap_err()), expected);
+    assert_eq!(message(handle.velocity(&u).unwrap_err()), expected);
+    assert_eq!(message(handle.mass(&u).unwrap_err()), expected);
+    assert!(handle.set_velocity(&mut u, 1.0, 0.0).is_err());
+}
+
+#[wasm_bindgen_test]
+fn render_to_buffer_views_the_rendered_pixels() {
+    let mut u = wasm_generic_space::Universe::empty(0.0, 1000.0, 0.01);
+    u.add_particle(3.0, 1.0, 0.0, 0.0, 1.0).unwrap();
+    let view = u.render_to_buffer(8, 4, 0.0, 0.0, 1.0).to_vec();
+    assert_eq!(view, u.render_pixels(8, 4, 0.0, 0.0, 1.0).to_vec());
+    assert_eq!(view[(8 + 3) * 4 + 3], 255);
+}
+
+#[wasm_bindgen_test]
+fn get_interleaved_views_the_filled_buffer() {
+    let mut u = wasm_generic_space::Universe::empty(0.0, 1000.0, 0.01);
+    u.add_particle(3.0, 1.0, 0.5, 0.25, 2.0).unwrap();
+    u.set_export_channels(3);
+    let view = u.get_interleaved().to_vec();
+    assert_eq!(view, vec![3.0, 1.0, 0.5, 0.25, 2.0]);
+    assert_eq!(view, u.interleaved().to_vec());
+}
+
+#[wasm_bindgen_test]
+fn exported_frame_round_trips_positions() {
+    let mut u = wasm_generic_space::Universe::new();
+    u.tick().unwrap();
+    u.set_export_channels(1);
+    let buffer = u.export_frame();
+    let positions = wasm_generic_space::Universe::parse_frame_positions(&buffer).unwrap();
+    assert_eq!(positions, u.get_positions());
+    assert_eq!(buffer.byte_length() as usize, 16 + 32 * u.particle_count() as usize);
+    let empty = js_sys::ArrayBuffer::new(4);
+    assert!(wasm_generic_space::Universe::parse_frame_positions(&empty).is_err());
+}
+
+#[wasm_bindgen_test]
+fn capabilities_is_well_formed() {
+    let caps = wasm_generic_space::capabilities();
+    let get = |key: &str| js_sys::Reflect::get(&caps, &key.into()).unwrap();
+    assert!(get("simd").as_bool().is_some());
+    assert!(get("threads").as_bool().is_some());
+    assert_eq!(get("version").as_string().unwrap(), env!("CARGO_PKG_VERSION"));
+}
+
+#[wasm_bindgen_test]
+fn set_mass_rejects_negative_and_non_finite_masses() {
+    let mut u = wasm_generic_space::Universe::empty(1.0, 1000.0, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    assert!(u.set_mass(0, -1.0).is_err());
+    assert!(u.set_mass(0, f64::NAN).is_err());
+    assert_eq!(u.get_masses(), vec![1.0]);
+}
+
+#[wasm_bindgen_test]
+fn load_blob_rejects_malformed_buffers() {
+    let mut u = wasm_generic_space::Universe::empty(1.0, 1000.0, 0.01);
+    u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+    assert!(u.load_blob(&[3, 0, 0, 0, 1, 2]).is_err());
+    assert_eq!(u.particle_count(), 1);
+}
+
+#[cfg(feature = "scheduling")]
+mod scheduling {
+    use futures::Future;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_futures::JsFuture;
+    use wasm_bindgen_test::*;
+    use wasm_generic_space::{Universe, UniverseSet};
+
+    // Behind an Rc so the universe stays in place while a run ticks it
+    fn pair() -> Rc<RefCell<Universe>> {
+        let mut u = Universe::empty(1.0, 1000.0, 0.01);
+        u.add_particle(-10.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+        u.add_particle(10.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+        Rc::new(RefCell::new(u))
+    }
+
+    #[wasm_bindgen_test(async)]
+    fn tick_async_runs_every_slice() -> impl Future<Item = (), Error = JsValue> {
+        let u = pair();
+        let run = u.borrow_mut().tick_async(10, 3);
+        let second = u.borrow_mut().tick_async(5, 1);
+        JsFuture::from(second)
+            .then(|second| {
+                assert!(second.is_err());
+                JsFuture::from(run)
+            })
+            .map(move |elapsed| {
+                assert_eq!(elapsed.as_f64(), Some(10.0));
+                assert_eq!(u.borrow().simulation_time(), 10.0);
+                assert!(!u.borrow_mut().cancel_async());
+            })
+    }
+
+    #[wasm_bindgen_test(async)]
+    fn cancel_async_stops_at_the_next_slice() -> impl Future<Item = (), Error = JsValue> {
+        let u = pair();
+        let run = u.borrow_mut().tick_async(1000, 1);
+        assert!(u.borrow_mut().cancel_async());
+        JsFuture::from(run).map(move |elapsed| {
+            let elapsed = elapsed.as_f64().unwrap();
+            assert!(elapsed < 1000.0);
+            assert_eq!(u.borrow().simulation_time(), elapsed);
+        })
+    }
+
+    #[wasm_bindgen_test(async)]
+    fn tick_async_refuses_cull_callbacks() -> impl Future<Item = (), Error = JsValue> {
+        let u = pair();
+        let f = js_sys::Function::new_with_args("data", "return [];");
+        u.borrow_mut().set_cull_callback(&f);
+        let run = u.borrow_mut().tick_async(10, 1);
+        JsFuture::from(run).then(move |result| {
+            assert!(result.is_err());
+            assert_eq!(u.borrow().simulation_time(), 0.0);
+            Ok(())
+        })
+    }
+
+    #[wasm_bindgen_test(async)]
+    fn universe_set_refuses_a_universe_while_it_runs() -> impl Future<Item = (), Error = JsValue> {
+        let mut u = Box::new(Universe::empty(1.0, 1000.0, 0.01));
+        u.add_particle(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+        let run = u.tick_async(1000, 1);
+        let mut set = UniverseSet::new();
+        // Refusing frees the universe, so the run must stop instead of ticking freed memory
+        assert!(set.add(*u).is_err());
+        assert!(set.is_empty());
+        JsFuture::from(run).then(|result| {
+            assert!(result.is_err());
+            Ok(())
+        })
+    }
+}
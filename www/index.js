@@ -9,6 +9,14 @@ canvas.setAttribute("width", 4000);
 canvas.setAttribute("height", 4000);
 canvas.setAttribute("style", "position: absolute; x:0; y:0;");
 var ctx = canvas.getContext("2d");
+
+document.getElementById("benchmark").addEventListener("click", () => {
+  const result = universe.benchmark(20);
+  document.getElementById("benchmark-result").textContent =
+    `${Math.round(result.particle_ticks_per_second())} particle ticks/s, ` +
+    `${result.tick_ms_avg().toFixed(1)} ms/tick`;
+  result.free();
+});
 //alert(universe.tick().get(0).getX());
 //while(true){
   // sleep(1000);